@@ -0,0 +1,13 @@
+// Every file the game writes starts with the version it was written as, and the loaders refuse
+// any other version before reading the rest
+use serde::Deserialize;
+
+// the version a TOML file says it was written as. It is read on its own, since the rest of an
+// older or newer file may not parse at all
+pub(crate) fn read_version(text: &str) -> Result<u32, toml::de::Error> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    toml::from_str::<Version>(text).map(|file| file.version)
+}
@@ -0,0 +1,87 @@
+use crate::GPUSprite;
+
+// the glyph atlas: printable ASCII from ' ' to '~' in order, 16 glyphs to a row, each 8x8 pixels
+pub const FONT_ATLAS: &str = "content/font.png";
+const ATLAS_COLUMNS: usize = 16;
+const ATLAS_ROWS: usize = 6;
+const FIRST_GLYPH: char = ' ';
+const LAST_GLYPH: char = '~';
+
+// the region of the atlas a character is drawn from. Anything the atlas doesn't have is drawn as
+// a question mark
+pub fn glyph_region(c: char) -> [f32; 4] {
+    let c = if (FIRST_GLYPH..=LAST_GLYPH).contains(&c) {
+        c
+    } else {
+        '?'
+    };
+    let index = c as usize - FIRST_GLYPH as usize;
+    let (column, row) = (index % ATLAS_COLUMNS, index / ATLAS_COLUMNS);
+    [
+        column as f32 / ATLAS_COLUMNS as f32,
+        row as f32 / ATLAS_ROWS as f32,
+        1.0 / ATLAS_COLUMNS as f32,
+        1.0 / ATLAS_ROWS as f32,
+    ]
+}
+
+// where a line of text sits relative to the point it is placed at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// how wide a line of text is in world units. Every glyph is a square `size` units across
+pub fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size
+}
+
+// one sprite per character of a line of text, drawn from the glyph atlas left to right on the
+// line through `pos`, with `pos` at its left edge, middle or right edge
+pub fn text_sprites(
+    text: &str,
+    pos: [f32; 2],
+    size: f32,
+    align: Align,
+) -> impl Iterator<Item = GPUSprite> + '_ {
+    let left = match align {
+        Align::Left => pos[0],
+        Align::Center => pos[0] - text_width(text, size) / 2.0,
+        Align::Right => pos[0] - text_width(text, size),
+    };
+    text.chars().enumerate().map(move |(index, c)| GPUSprite {
+        screen_region: [left + index as f32 * size, pos[1], size, size],
+        sheet_region: glyph_region(c),
+    })
+}
+
+// as much of a line of text as fits in `width` world units
+pub fn truncated(text: &str, width: f32, size: f32) -> String {
+    text.chars().take((width / size) as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_follow_ascii_order() {
+        assert_eq!(glyph_region(' ')[..2], [0.0, 0.0]);
+        // 'A' is 33 glyphs in, the second glyph of the third row
+        assert_eq!(glyph_region('A')[..2], [1.0 / 16.0, 2.0 / 6.0]);
+        assert_eq!(glyph_region('é'), glyph_region('?'));
+    }
+
+    #[test]
+    fn text_is_laid_out_from_its_anchor() {
+        let sprites: Vec<_> = text_sprites("SCORE", [10.0, 0.0], 2.0, Align::Right).collect();
+        assert_eq!(sprites[0].screen_region, [0.0, 0.0, 2.0, 2.0]);
+        assert_eq!(sprites[4].sheet_region, glyph_region('E'));
+
+        let centered: Vec<_> = text_sprites("OK", [0.0, 0.0], 2.0, Align::Center).collect();
+        assert_eq!(centered[0].screen_region[0], -2.0);
+        assert_eq!(truncated("SCORE", 7.0, 2.0), "SCO");
+    }
+}
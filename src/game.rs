@@ -0,0 +1,693 @@
+use crate::grid::{GameGrid, Move};
+use crate::grid_generator::{
+    generate_grid, refill_grid, seeded_rng, shuffle_grid, spread_chocolate, GameRng,
+    MIN_STARTING_MOVES,
+};
+use crate::level::{GameMode, Level, Outcome, Progress};
+use crate::scoring::{move_bonus, time_bonus, MoveScore};
+use std::time::Duration;
+
+// something the player (or a bot) does to the game
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    // swap the candies in two neighboring spaces, given as (x, y)
+    Swap((usize, usize), (usize, usize)),
+    // let time pass on the game clock
+    Tick(Duration),
+    // take back the last move
+    Undo,
+    // make the last undone move again
+    Redo,
+    // stop the game clock. Nothing but Resume does anything until the game is resumed
+    Pause,
+    Resume,
+}
+
+// what came of an action
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    // the swap was made and the board has settled
+    Swapped {
+        // how many candies were blacked out, chain matches included
+        cleared: usize,
+        // the points the move scored, see MoveRecord::score for the breakdown
+        points: usize,
+        // whether the board ran out of moves afterwards and had to be shuffled
+        shuffled: bool,
+    },
+    // the swap didn't make a match, so the board was left as it was
+    Rejected,
+    // the spaces weren't two neighboring candies free to move
+    Invalid,
+    // time passed and the game goes on
+    Waited,
+    // this action ran the clock out. Moves can end the game too, by meeting the level's
+    // objectives, using up its moves or leaving an endless board stuck, which shows in
+    // GameState::outcome
+    GameOver,
+    // the move was taken back
+    Undone(Move),
+    // the move was made again
+    Redone(Move),
+    Paused,
+    Resumed,
+    // nothing happened: the game was already over or paused, or there was nothing to undo or
+    // redo
+    Ignored,
+}
+
+// the parts of the game a move changes
+#[derive(Clone)]
+struct Snapshot {
+    grid: GameGrid,
+    rng: GameRng,
+    score: usize,
+    progress: Progress,
+}
+
+// one move the player made, with the game as it was before and after it. Keeping the whole board
+// keeps every clear and refill the move caused, and keeping the RNG means redoing a move drops in
+// the same candies it did the first time
+#[derive(Clone)]
+pub struct MoveRecord {
+    pub swap: Move,
+    // how many candies the move blacked out
+    pub cleared: usize,
+    pub score: MoveScore,
+    before: Snapshot,
+    after: Snapshot,
+}
+
+// everything that makes up one game, without anything to do with how it is shown or controlled
+#[derive(Clone)]
+pub struct GameState {
+    level: Level,
+    grid: GameGrid,
+    rng: GameRng,
+    seed: u64,
+    score: usize,
+    progress: Progress,
+    // how much of the game clock has run. It only runs while the game is being played
+    elapsed: Duration,
+    paused: bool,
+    // set once the game is over
+    outcome: Option<Outcome>,
+    // the points given for the time or moves left when the game ended
+    bonus: usize,
+    // the moves made so far, oldest first
+    history: Vec<MoveRecord>,
+    // moves that were undone, most recently undone last, ready to be redone
+    undone: Vec<MoveRecord>,
+}
+
+impl GameState {
+    // start a classic timed game on a board of the given size. The same seed always gives the
+    // same game for the same actions
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        GameState::with_level(Level::timed(width, height), seed)
+    }
+
+    // start a game of the given level, filling in the rest of its board from the seed
+    pub fn with_level(level: Level, seed: u64) -> Self {
+        let mut grid = level.board.clone();
+        let mut rng = seeded_rng(seed);
+        if !generate_grid(&mut grid, &mut rng, MIN_STARTING_MOVES) {
+            log::warn!(
+                "Couldn't generate a board with {} moves!",
+                MIN_STARTING_MOVES
+            );
+        }
+        GameState {
+            level,
+            grid,
+            rng,
+            seed,
+            score: 0,
+            progress: Progress::default(),
+            elapsed: Duration::ZERO,
+            paused: false,
+            outcome: None,
+            bonus: 0,
+            history: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // pick a game back up from where it was saved. Nothing from before is left to undo
+    pub(crate) fn resumed(
+        level: Level,
+        grid: GameGrid,
+        rng: GameRng,
+        seed: u64,
+        score: usize,
+        progress: Progress,
+        elapsed: Duration,
+    ) -> Self {
+        GameState {
+            level,
+            grid,
+            rng,
+            seed,
+            score,
+            progress,
+            elapsed,
+            paused: false,
+            outcome: None,
+            bonus: 0,
+            history: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // put back how a resumed game ended, for one that was saved once it was over
+    pub(crate) fn set_ending(&mut self, outcome: Option<Outcome>, bonus: usize) {
+        self.outcome = outcome;
+        self.bonus = bonus;
+    }
+
+    pub(crate) fn rng(&self) -> &GameRng {
+        &self.rng
+    }
+
+    pub fn grid(&self) -> &GameGrid {
+        &self.grid
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn score(&self) -> usize {
+        self.score
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.level.mode
+    }
+
+    // how much of the game clock has run
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    // the time left on the clock, or None if the game isn't timed
+    pub fn time_left(&self) -> Option<Duration> {
+        self.level
+            .mode
+            .time_limit()
+            .map(|limit| limit.saturating_sub(self.elapsed))
+    }
+
+    // the moves the player has left, or None if they aren't limited
+    pub fn moves_left(&self) -> Option<usize> {
+        self.level
+            .mode
+            .move_limit()
+            .map(|limit| limit.saturating_sub(self.progress.moves))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub fn bonus(&self) -> usize {
+        self.bonus
+    }
+
+    fn objectives_met(&self) -> bool {
+        self.level
+            .objectives_met(&self.grid, self.score, &self.progress)
+    }
+
+    // end the game. A win pays out for whatever time or moves are left
+    fn end(&mut self) {
+        if self.objectives_met() {
+            self.bonus = time_bonus(self.time_left().unwrap_or_default())
+                + move_bonus(self.moves_left().unwrap_or_default());
+            self.score += self.bonus;
+            self.outcome = Some(Outcome::Won {
+                stars: self.level.stars_for(self.score),
+            });
+        } else {
+            self.outcome = Some(Outcome::Lost);
+        }
+    }
+
+    // the moves made so far, oldest first
+    pub fn history(&self) -> &[MoveRecord] {
+        &self.history
+    }
+
+    // the most waves of clears any one move set off, chain matches included
+    pub fn best_combo(&self) -> usize {
+        self.history
+            .iter()
+            .map(|record| record.score.cascades.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            grid: self.grid.clone(),
+            rng: self.rng.clone(),
+            score: self.score,
+            progress: self.progress.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.grid = snapshot.grid.clone();
+        self.rng = snapshot.rng.clone();
+        self.score = snapshot.score;
+        self.progress = snapshot.progress.clone();
+    }
+
+    pub fn step(&mut self, action: Action) -> StepResult {
+        if self.is_over() || (self.paused && action != Action::Resume) {
+            return StepResult::Ignored;
+        }
+        match action {
+            Action::Swap(first, second) => {
+                if !self.grid.can_swap(first, second) {
+                    return StepResult::Invalid;
+                }
+                let before = self.snapshot();
+                if !self.grid.try_swap(first, second) {
+                    return StepResult::Rejected;
+                }
+                let (mut score, cleared, shuffled) = self.settle(&[first, second]);
+                if !self.level.mode.keeps_score() {
+                    score = MoveScore::default();
+                }
+                self.score += score.total;
+                self.progress.moves += 1;
+                log::info!("{:?} scored {}", Move { first, second }, score);
+                let points = score.total;
+
+                // a new move starts a new line of history, so nothing undone can be redone
+                self.undone.clear();
+                self.history.push(MoveRecord {
+                    swap: Move { first, second },
+                    cleared,
+                    score,
+                    before,
+                    after: self.snapshot(),
+                });
+
+                // the level is won as soon as its objectives are met, and over once the
+                // moves run out either way. An endless board isn't shuffled, so it is over once
+                // there is nothing left to swap
+                if self.level.mode.can_end()
+                    && ((!self.level.objectives.is_empty() && self.objectives_met())
+                        || self.moves_left() == Some(0)
+                        || (self.level.mode == GameMode::Endless
+                            && self.grid.find_valid_moves().is_empty()))
+                {
+                    self.end();
+                }
+                StepResult::Swapped {
+                    cleared,
+                    points,
+                    shuffled,
+                }
+            }
+            Action::Undo => match self.history.pop() {
+                Some(record) => {
+                    self.restore(&record.before);
+                    let swap = record.swap;
+                    self.undone.push(record);
+                    StepResult::Undone(swap)
+                }
+                None => StepResult::Ignored,
+            },
+            Action::Redo => match self.undone.pop() {
+                Some(record) => {
+                    self.restore(&record.after);
+                    let swap = record.swap;
+                    self.history.push(record);
+                    StepResult::Redone(swap)
+                }
+                None => StepResult::Ignored,
+            },
+            Action::Pause => {
+                self.paused = true;
+                StepResult::Paused
+            }
+            Action::Resume if self.paused => {
+                self.paused = false;
+                StepResult::Resumed
+            }
+            Action::Resume => StepResult::Ignored,
+            Action::Tick(time) => {
+                self.elapsed += time;
+                if self.time_left() == Some(Duration::ZERO) {
+                    self.end();
+                    StepResult::GameOver
+                } else {
+                    StepResult::Waited
+                }
+            }
+        }
+    }
+
+    // black out matches, let the candies above fall and refill from the top, repeating until
+    // the board is stable so chain matches are caught, then shuffle if no moves are left.
+    // Ingredients that reach the bottom on the way are collected, and if no chocolate was broken
+    // it spreads before the shuffle check.
+    // `swapped` is the pair of spaces the player just swapped. Returns what the move scored, how
+    // many candies were blacked out and whether the board was shuffled
+    fn settle(&mut self, mut swapped: &[(usize, usize)]) -> (MoveScore, usize, bool) {
+        let mut score = MoveScore::default();
+        let mut cleared = 0;
+        let mut chocolate = 0;
+
+        // a color bomb goes off as soon as it is swapped
+        if let &[first, second] = swapped {
+            if let Some(clear) = self.grid.detonate_swap(first, second) {
+                score.add(&clear);
+                cleared += clear.cleared;
+                chocolate += clear.chocolate;
+                self.progress.collect(&clear.colors);
+                self.grid.collapse();
+                refill_grid(&mut self.grid, &mut self.rng);
+                swapped = &[];
+            }
+        }
+
+        loop {
+            let clear = self.grid.clear_matches(swapped);
+            if !clear.is_empty() {
+                score.add(&clear);
+                cleared += clear.cleared;
+                chocolate += clear.chocolate;
+                self.progress.collect(&clear.colors);
+                self.grid.collapse();
+            }
+            // an ingredient can land on the bottom without anything matching, if a swap moved it
+            let collected = self.grid.collect_ingredients();
+            if clear.is_empty() && collected == 0 {
+                break;
+            }
+            if collected > 0 {
+                self.progress.ingredients += collected;
+                self.grid.collapse();
+            }
+            refill_grid(&mut self.grid, &mut self.rng);
+            // later matches fall into place on their own, so the swap no longer decides anything
+            swapped = &[];
+        }
+
+        if chocolate == 0 {
+            spread_chocolate(&mut self.grid, &mut self.rng);
+        }
+
+        // the board is stable, but it has to leave the player something to do. Except in an
+        // endless game, which ends there instead
+        let shuffled =
+            self.level.mode != GameMode::Endless && self.grid.find_valid_moves().is_empty();
+        if shuffled && !shuffle_grid(&mut self.grid, &mut self.rng) {
+            log::warn!("Couldn't find a playable shuffle!");
+        }
+        (score, cleared, shuffled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::CandyColor;
+    use crate::level::{Objective, GAME_DURATION};
+
+    #[test]
+    fn valid_swap_scores_and_rejected_swap_changes_nothing() {
+        let mut game = GameState::new(8, 8, 3);
+
+        // find a swap next to a valid move that doesn't make a match itself
+        let board = game.grid().clone();
+        let rejected = (0..8)
+            .flat_map(|y| (0..7).map(move |x| ((x, y), (x + 1, y))))
+            .find(|&(first, second)| !board.swap_makes_match(first, second))
+            .unwrap();
+        assert_eq!(
+            game.step(Action::Swap(rejected.0, rejected.1)),
+            StepResult::Rejected
+        );
+        assert!(*game.grid() == board);
+        assert_eq!(game.score(), 0);
+
+        let valid = board.find_valid_moves()[0];
+        match game.step(Action::Swap(valid.first, valid.second)) {
+            StepResult::Swapped {
+                cleared, points, ..
+            } => {
+                assert!(cleared >= 3);
+                assert!(points >= 60);
+                assert_eq!(game.score(), points);
+                assert_eq!(game.history()[0].score.total, points);
+                assert!(game.best_combo() >= 1);
+            }
+            other => panic!("expected a swap, got {:?}", other),
+        }
+        assert!(game.grid().find_matches().is_empty());
+    }
+
+    #[test]
+    fn swaps_must_be_between_neighbors() {
+        let mut game = GameState::new(8, 8, 3);
+        assert_eq!(game.step(Action::Swap((0, 0), (2, 0))), StepResult::Invalid);
+        assert_eq!(game.step(Action::Swap((0, 0), (1, 1))), StepResult::Invalid);
+    }
+
+    #[test]
+    fn game_ends_when_the_clock_runs_out() {
+        let mut game = GameState::new(8, 8, 3);
+        assert_eq!(
+            game.step(Action::Tick(GAME_DURATION - Duration::from_secs(1))),
+            StepResult::Waited
+        );
+        assert_eq!(game.time_left(), Some(Duration::from_secs(1)));
+        assert_eq!(
+            game.step(Action::Tick(Duration::from_secs(1))),
+            StepResult::GameOver
+        );
+        assert!(game.is_over());
+        // the classic game has no objectives, so lasting to the end wins, but the clock ran out
+        // so there is no time left to pay out for
+        assert!(matches!(game.outcome(), Some(Outcome::Won { .. })));
+        assert_eq!(game.bonus(), 0);
+
+        let valid = game.grid().find_valid_moves()[0];
+        assert_eq!(
+            game.step(Action::Swap(valid.first, valid.second)),
+            StepResult::Ignored
+        );
+    }
+
+    #[test]
+    fn the_clock_stops_while_paused() {
+        let mut game = GameState::new(8, 8, 3);
+        assert_eq!(game.step(Action::Resume), StepResult::Ignored);
+        assert_eq!(game.step(Action::Pause), StepResult::Paused);
+        assert!(game.is_paused());
+        assert_eq!(game.step(Action::Tick(GAME_DURATION)), StepResult::Ignored);
+        assert_eq!(game.time_left(), Some(GAME_DURATION));
+
+        // no moves can be made either
+        let board = game.grid().clone();
+        let valid = board.find_valid_moves()[0];
+        assert_eq!(
+            game.step(Action::Swap(valid.first, valid.second)),
+            StepResult::Ignored
+        );
+        assert!(*game.grid() == board);
+
+        assert_eq!(game.step(Action::Resume), StepResult::Resumed);
+        assert_eq!(
+            game.step(Action::Tick(Duration::from_secs(1))),
+            StepResult::Waited
+        );
+        assert_eq!(
+            game.time_left(),
+            Some(GAME_DURATION - Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_board() {
+        let mut game = GameState::new(8, 8, 5);
+        let start = game.grid().clone();
+        assert_eq!(game.step(Action::Undo), StepResult::Ignored);
+
+        let first = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(first.first, first.second));
+        let after_first = game.grid().clone();
+        let score_after_first = game.score();
+
+        assert_eq!(game.step(Action::Undo), StepResult::Undone(first));
+        assert!(*game.grid() == start);
+        assert_eq!(game.score(), 0);
+
+        assert_eq!(game.step(Action::Redo), StepResult::Redone(first));
+        assert!(*game.grid() == after_first);
+        assert_eq!(game.score(), score_after_first);
+        assert_eq!(game.history().len(), 1);
+
+        // undoing and making a different move drops the move that could have been redone
+        game.step(Action::Undo);
+        let other = *game.grid().find_valid_moves().last().unwrap();
+        game.step(Action::Swap(other.first, other.second));
+        assert_eq!(game.step(Action::Redo), StepResult::Ignored);
+    }
+
+    #[test]
+    fn redone_moves_refill_the_same_way() {
+        let mut game = GameState::new(8, 8, 9);
+        let first = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(first.first, first.second));
+        let after = game.grid().clone();
+
+        // making the same move again after an undo replays the same refills
+        game.step(Action::Undo);
+        game.step(Action::Swap(first.first, first.second));
+        assert!(*game.grid() == after);
+    }
+
+    // a timed level that only asks for the given objectives
+    fn level_with(objectives: Vec<Objective>) -> Level {
+        Level {
+            objectives,
+            ..Level::timed(8, 8)
+        }
+    }
+
+    #[test]
+    fn meeting_the_objectives_wins_early_with_a_time_bonus() {
+        let mut game = GameState::with_level(level_with(vec![Objective::Score(1)]), 3);
+        game.step(Action::Tick(Duration::from_secs(5)));
+        let valid = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(valid.first, valid.second));
+        assert!(matches!(game.outcome(), Some(Outcome::Won { .. })));
+        assert!(game.bonus() > 0);
+    }
+
+    #[test]
+    fn running_out_of_moves_loses() {
+        let level = Level {
+            mode: GameMode::Moves(1),
+            ..level_with(vec![Objective::Score(usize::MAX)])
+        };
+        let mut game = GameState::with_level(level, 3);
+        assert_eq!(game.moves_left(), Some(1));
+        let valid = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(valid.first, valid.second));
+        assert_eq!(game.moves_left(), Some(0));
+        assert_eq!(game.outcome(), Some(Outcome::Lost));
+        assert_eq!(game.bonus(), 0);
+    }
+
+    #[test]
+    fn winning_with_moves_left_pays_out_for_them() {
+        let level = Level {
+            mode: GameMode::Moves(10),
+            ..level_with(vec![Objective::Score(1)])
+        };
+        let mut game = GameState::with_level(level, 3);
+        let valid = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(valid.first, valid.second));
+        assert!(matches!(game.outcome(), Some(Outcome::Won { .. })));
+        assert_eq!(game.bonus(), move_bonus(9));
+    }
+
+    #[test]
+    fn zen_games_keep_no_score_and_never_end() {
+        let level = Level {
+            mode: GameMode::Zen,
+            ..level_with(vec![Objective::Score(1)])
+        };
+        let mut game = GameState::with_level(level, 3);
+        assert_eq!(game.time_left(), None);
+        assert_eq!(game.moves_left(), None);
+        for _ in 0..5 {
+            let valid = game.grid().find_valid_moves()[0];
+            assert!(matches!(
+                game.step(Action::Swap(valid.first, valid.second)),
+                StepResult::Swapped { points: 0, .. }
+            ));
+        }
+        assert_eq!(
+            game.step(Action::Tick(Duration::from_secs(3600))),
+            StepResult::Waited
+        );
+        assert_eq!(game.score(), 0);
+        assert!(!game.is_over());
+    }
+
+    #[test]
+    fn endless_games_end_when_the_board_is_stuck() {
+        // a small board runs out of moves quickly
+        let mut game = GameState::with_level(Level::classic(4, 4, GameMode::Endless), 3);
+        for _ in 0..1000 {
+            if game.is_over() {
+                break;
+            }
+            let valid = game.grid().find_valid_moves()[0];
+            match game.step(Action::Swap(valid.first, valid.second)) {
+                StepResult::Swapped { shuffled, .. } => assert!(!shuffled),
+                other => panic!("expected a swap, got {:?}", other),
+            }
+        }
+        assert!(game.grid().find_valid_moves().is_empty());
+        assert!(matches!(game.outcome(), Some(Outcome::Won { .. })));
+    }
+
+    #[test]
+    fn ingredients_are_collected_at_the_bottom() {
+        let mut level = level_with(vec![Objective::Ingredients]);
+        level.board.fill_space(4, 7, CandyColor::Ingredient);
+        let mut game = GameState::with_level(level, 3);
+        assert_eq!(game.grid().ingredients_left(), 1);
+
+        let valid = game
+            .grid()
+            .find_valid_moves()
+            .into_iter()
+            .find(|possible| possible.first != (4, 7) && possible.second != (4, 7))
+            .unwrap();
+        game.step(Action::Swap(valid.first, valid.second));
+        assert_eq!(game.progress().ingredients, 1);
+        assert_eq!(game.grid().ingredients_left(), 0);
+        assert!(matches!(game.outcome(), Some(Outcome::Won { .. })));
+    }
+
+    #[test]
+    fn blacking_out_candies_clears_jelly() {
+        let mut level = level_with(vec![Objective::ClearJelly]);
+        for x in 0..8 {
+            for y in 0..8 {
+                level.board.set_jelly(x, y, 1);
+            }
+        }
+        let mut game = GameState::with_level(level, 3);
+        let valid = game.grid().find_valid_moves()[0];
+        game.step(Action::Swap(valid.first, valid.second));
+        assert!(game.grid().jelly_left() < 64);
+        assert!(!game.is_over());
+    }
+}
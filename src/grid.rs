@@ -1,172 +1,1358 @@
-#[derive(Copy, Clone)]
+use serde::{Deserialize, Serialize};
+
+// The six candy colors on the sprite sheet, the ingredients and blockers levels place, plus the
+// two non-candy states a space can be in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandyColor {
+    White,
+    DarkBlue,
+    LightBlue,
+    LightOrange,
+    DarkOrange,
+    WhiteOrange,
+    // falls like a candy but never matches or gets blacked out. It leaves the board once it
+    // reaches the bottom
+    Ingredient,
+    // never moves, matches or breaks
+    Stone,
+    // never moves or matches, but breaks when a match is made next to it or a special candy hits
+    // it. Spreads onto a neighboring candy after any move that doesn't break some
+    Chocolate,
+    // nothing has been placed in the space yet
+    Empty,
+    // the candy in the space was blacked out by a match
+    Cleared,
+}
+
+// every color a space can be filled with, in sprite sheet order
+pub const CANDY_COLORS: [CandyColor; 6] = [
+    CandyColor::White,
+    CandyColor::DarkBlue,
+    CandyColor::LightBlue,
+    CandyColor::LightOrange,
+    CandyColor::DarkOrange,
+    CandyColor::WhiteOrange,
+];
+
+impl CandyColor {
+    pub fn is_candy(self) -> bool {
+        !matches!(
+            self,
+            CandyColor::Empty | CandyColor::Cleared | CandyColor::Stone | CandyColor::Chocolate
+        )
+    }
+
+    // the region of the sprite sheet that this color is drawn from
+    pub fn sheet_region(self) -> [f32; 4] {
+        let row = match self {
+            CandyColor::White => 0.0,
+            CandyColor::DarkBlue => 16.0,
+            CandyColor::LightBlue => 32.0,
+            CandyColor::LightOrange => 48.0,
+            CandyColor::DarkOrange => 64.0,
+            CandyColor::WhiteOrange => 80.0,
+            CandyColor::Cleared => 96.0,
+            CandyColor::Ingredient => return [32.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+            CandyColor::Stone => return [0.0 / 80.0, 128.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+            CandyColor::Chocolate => return [16.0 / 80.0, 128.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+            // the column next to the candies is transparent, so empty spaces draw nothing
+            CandyColor::Empty => return [8.0 / 80.0, 0.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+        };
+        [0.0 / 80.0, row / 160.0, 8.0 / 80.0, 8.0 / 160.0]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+    // a horizontal and a vertical run merged into an L, T or + shape
+    Cross,
+}
+
+// a group of three or more matching candies
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub color: CandyColor,
+    pub orientation: Orientation,
+    // the length of the longest straight run in the match
+    pub length: usize,
+    // the (x, y) of every space in the match, read from the top left
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Match {
+    fn overlaps(&self, other: &Match) -> bool {
+        self.cells.iter().any(|cell| other.cells.contains(cell))
+    }
+
+    fn merge(mut self, other: Match) -> Match {
+        if self.orientation != other.orientation || other.orientation == Orientation::Cross {
+            self.orientation = Orientation::Cross;
+        }
+        self.length = self.length.max(other.length);
+        for cell in other.cells {
+            if !self.cells.contains(&cell) {
+                self.cells.push(cell);
+            }
+        }
+        self.cells.sort_by_key(|&(x, y)| (y, x));
+        self
+    }
+}
+
+// special candies are made by big matches and clear more than their own space when they are blacked out
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Special {
+    None,
+    // clears its whole row
+    StripedHorizontal,
+    // clears its whole column
+    StripedVertical,
+    // clears the 3x3 square around it
+    Wrapped,
+    // clears every candy of the color it is swapped with
+    ColorBomb,
+}
+
+impl Special {
+    // the special candy a match leaves behind, if it is big enough to make one
+    fn made_by(found: &Match) -> Special {
+        if found.length >= 5 {
+            Special::ColorBomb
+        } else if found.orientation == Orientation::Cross {
+            Special::Wrapped
+        } else if found.length == 4 {
+            // as in the rest of the genre, a four in a row makes a candy striped across the row's direction
+            match found.orientation {
+                Orientation::Horizontal => Special::StripedVertical,
+                _ => Special::StripedHorizontal,
+            }
+        } else {
+            Special::None
+        }
+    }
+}
+
+// something covering the candy in a space. A covered candy stays put: it can't be swapped and
+// doesn't fall, so the candies above it land on top of it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blocker {
+    None,
+    // breaks along with the candy under it when the candy is blacked out
+    Ice,
+    // comes off the first time the candy is matched or hit, leaving the candy behind
+    Lock,
+}
+
+impl Blocker {
+    // the region of the sprite sheet drawn over a covered candy
+    pub fn sheet_region(self) -> [f32; 4] {
+        match self {
+            Blocker::None => CandyColor::Empty.sheet_region(),
+            Blocker::Ice => [32.0 / 80.0, 128.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+            Blocker::Lock => [48.0 / 80.0, 128.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+        }
+    }
+}
+
+// a swap the player can make, given as the (x, y) of both spaces
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub first: (usize, usize),
+    pub second: (usize, usize),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Space {
-    color: &'static str, // Use a string slice for color
+    color: CandyColor,
+    special: Special,
+    // whether the space is part of the board. Spaces that aren't are holes, which stay empty,
+    // never match and let candies fall straight through them
     filled: bool,
+    // layers of jelly under the candy. One comes off each time the candy on top is blacked out
+    jelly: u8,
+    blocker: Blocker,
 }
 
 impl Space {
-    fn new(color: &'static str) -> Self {
+    fn new(color: CandyColor) -> Self {
         Space {
             color,
-            filled: false,
+            special: Special::None,
+            filled: true,
+            jelly: 0,
+            blocker: Blocker::None,
         }
     }
+
+    // whether this space can be part of a match. Color bombs keep the color of the match
+    // that made them, but never match on it
+    fn is_matchable(&self) -> bool {
+        self.filled
+            && self.color.is_candy()
+            && self.color != CandyColor::Ingredient
+            && self.special != Special::ColorBomb
+    }
+
+    // whether the space keeps its content when the candies around it fall
+    fn is_fixed(&self) -> bool {
+        matches!(self.color, CandyColor::Stone | CandyColor::Chocolate)
+            || self.blocker != Blocker::None
+    }
+
+    fn sheet_region(&self) -> [f32; 4] {
+        let mut region = self.color.sheet_region();
+        // special candies sit in the columns to the right of their plain candy
+        match self.special {
+            Special::None => {}
+            Special::StripedHorizontal => region[0] = 16.0 / 80.0,
+            Special::StripedVertical => region[0] = 32.0 / 80.0,
+            Special::Wrapped => region[0] = 48.0 / 80.0,
+            Special::ColorBomb => region = [64.0 / 80.0, 0.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+        }
+        region
+    }
 }
 
-pub struct GameGrid {
-    grid: [[Space; 10]; 20],
+// everything that happened when a group of spaces was blacked out
+#[derive(Clone, Debug, Default)]
+pub struct Clear {
+    pub matches: Vec<Match>,
+    // the special candies that went off, and where they were
+    pub detonations: Vec<((usize, usize), Special)>,
+    // the special candies made by the matches, and where they were placed
+    pub created: Vec<((usize, usize), Special)>,
+    // how many spaces were blacked out, including by detonations
+    pub cleared: usize,
+    // how many of those a detonation blacked out, rather than a match or a swapped color bomb
+    pub blasted: usize,
+    // the color of every candy that was blacked out
+    pub colors: Vec<CandyColor>,
+    // how many layers of jelly were cleared from under the blacked out candies
+    pub jelly: usize,
+    // how many locks came off
+    pub unlocked: usize,
+    // how many chocolate spaces were broken
+    pub chocolate: usize,
+}
+
+impl Clear {
+    pub fn is_empty(&self) -> bool {
+        self.cleared == 0 && self.created.is_empty() && self.unlocked == 0
+    }
+}
+
+// whether new candies can be picked from `colors`: there have to be at least three, and a color
+// listed twice would leave fewer different ones than it looks
+fn enough_colors(colors: &[CandyColor]) -> bool {
+    colors.len() >= 3
+        && colors
+            .iter()
+            .enumerate()
+            .all(|(index, color)| !colors[..index].contains(color))
 }
 
-pub fn screen_to_grid(x: f32, y: f32) -> (usize, usize) {
-    let grid_x = (x as usize - 80) / 8;
-    let grid_y = 19 - (y as usize) / 8;
-    (grid_x, grid_y)
+// the size of one board space in world units
+pub const CELL_SIZE: f32 = 8.0;
+// the board size used when none is given
+pub const DEFAULT_WIDTH: usize = 10;
+pub const DEFAULT_HEIGHT: usize = 20;
+// the fewest and most spaces a board can have across or down. Fewer than 3 leaves no room for a
+// match, and past 50 the candies are too small to make out once the board is fit to the window
+pub const MIN_BOARD_SIDE: usize = 3;
+pub const MAX_BOARD_SIDE: usize = 50;
+// the world position of the bottom left corner of the board
+pub const BOARD_ORIGIN: [f32; 2] = [80.0, 0.0];
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameGrid {
+    width: usize,
+    height: usize,
+    // indexed as grid[y][x], with row 0 at the top of the board
+    grid: Vec<Vec<Space>>,
+    // the colors new candies are picked from
+    colors: Vec<CandyColor>,
 }
 
 impl GameGrid {
-    pub fn new() -> Self {
-        let mut grid = [[Space::new("empty"); 10]; 20];
+    pub fn with_size(width: usize, height: usize) -> Self {
+        // Initialize the grid with empty spaces
+        let grid = vec![vec![Space::new(CandyColor::Empty); width]; height];
 
-        // Initialize the grid with space objects
-        let mut y_cord = 518.0;
-        let mut x_cord = 98.0;
-        for row in 0..20 {
-            for col in 0..10 {
-                grid[row][col] = Space::new("nah");
-                y_cord -= 88.0;
-            }
-            x_cord += 98.0;
-            y_cord = 518.0;
+        GameGrid {
+            width,
+            height,
+            grid,
+            colors: CANDY_COLORS.to_vec(),
         }
+    }
 
-        GameGrid { grid }
+    pub fn width(&self) -> usize {
+        self.width
     }
 
-    pub fn print_grid(&self) {
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn colors(&self) -> &[CandyColor] {
+        &self.colors
+    }
+
+    // limit the colors new candies are picked from. Boards need at least three different colors,
+    // or there would be no way to fill them without making matches
+    pub fn set_colors(&mut self, colors: Vec<CandyColor>) {
+        assert!(
+            enough_colors(&colors),
+            "a board needs at least three colors, each listed once"
+        );
+        self.colors = colors;
+    }
+
+    // whether the board has as many rows and columns as it says, and enough colors to refill
+    // from. Only a board read from outside the game can be anything else
+    pub(crate) fn is_well_formed(&self) -> bool {
+        (MIN_BOARD_SIDE..=MAX_BOARD_SIDE).contains(&self.width)
+            && (MIN_BOARD_SIDE..=MAX_BOARD_SIDE).contains(&self.height)
+            && self.grid.len() == self.height
+            && self.grid.iter().all(|row| row.len() == self.width)
+            && enough_colors(&self.colors)
+            && self.colors.iter().all(|color| CANDY_COLORS.contains(color))
+    }
+
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    // the world size of the whole board
+    pub fn screen_size(&self) -> [f32; 2] {
+        [
+            self.width as f32 * CELL_SIZE,
+            self.height as f32 * CELL_SIZE,
+        ]
+    }
+
+    // convert a world position to the space under it, if there is one
+    pub fn screen_to_grid(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let col = ((x - BOARD_ORIGIN[0]) / CELL_SIZE).floor();
+        let row_from_bottom = ((y - BOARD_ORIGIN[1]) / CELL_SIZE).floor();
+        if col < 0.0 || row_from_bottom < 0.0 {
+            return None;
+        }
+        let (col, row_from_bottom) = (col as usize, row_from_bottom as usize);
+        if col >= self.width || row_from_bottom >= self.height {
+            return None;
+        }
+        Some((col, self.height - 1 - row_from_bottom))
+    }
+
+    // the world position of the bottom left corner of a space
+    pub fn grid_to_screen(&self, x: usize, y: usize) -> [f32; 2] {
+        [
+            BOARD_ORIGIN[0] + x as f32 * CELL_SIZE,
+            BOARD_ORIGIN[1] + (self.height - 1 - y) as f32 * CELL_SIZE,
+        ]
+    }
+
+    // log which spaces are filled, one row per line, 1 for filled and 0 for empty
+    pub fn log_grid(&self) {
         for row in &self.grid {
-            for space in row {
-                if space.filled {
-                    print!("1 "); // You can change this to any character or representation for filled spaces
-                } else {
-                    print!("0 "); // You can change this to any character or representation for empty spaces
-                }
-            }
-            println!();
+            let line: Vec<&str> = row
+                .iter()
+                .map(|space| if space.filled { "1" } else { "0" })
+                .collect();
+            log::debug!("{}", line.join(" "));
         }
     }
 
-    pub fn fill_space(&mut self, x: usize, y: usize, color: &'static str) {
-        if x < 10 && y < 20 {
-            self.grid[y][x].filled = true;
+    // put a candy, or anything else, in a space. Holes stay empty
+    pub fn fill_space(&mut self, x: usize, y: usize, color: CandyColor) {
+        if self.in_bounds(x, y) && self.grid[y][x].filled {
             self.grid[y][x].color = color;
+            self.grid[y][x].special = Special::None;
         }
     }
 
-    pub fn print_space(&self, x: usize, y: usize) {
-        if x < 10 && y < 20 {
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.grid[y][x].filled
+    }
+
+    // cut a space out of the board, for boards that aren't rectangles
+    pub fn make_hole(&mut self, x: usize, y: usize) {
+        if self.in_bounds(x, y) {
+            self.grid[y][x] = Space::new(CandyColor::Empty);
+            self.grid[y][x].filled = false;
+        }
+    }
+
+    pub fn log_space(&self, x: usize, y: usize) {
+        if self.in_bounds(x, y) {
             let space = &self.grid[y][x];
-            println!(
-                "x: {}, y: {}, color: {}, filled: {}",
-                x, y, space.color, space.filled
+            log::debug!(
+                "x: {}, y: {}, color: {:?}, special: {:?}, filled: {}, jelly: {}, blocker: {:?}",
+                x,
+                y,
+                space.color,
+                space.special,
+                space.filled,
+                space.jelly,
+                space.blocker
             );
         } else {
-            println!("Invalid indices");
+            log::debug!("Invalid indices");
+        }
+    }
+
+    pub fn color(&self, x: usize, y: usize) -> CandyColor {
+        self.grid[y][x].color
+    }
+
+    pub fn special(&self, x: usize, y: usize) -> Special {
+        self.grid[y][x].special
+    }
+
+    pub fn blocker(&self, x: usize, y: usize) -> Blocker {
+        self.grid[y][x].blocker
+    }
+
+    // cover the candy in a space. Blockers only go over candies, so the space has to be filled in
+    // before the game starts, or be left empty for the generator to fill
+    pub fn set_blocker(&mut self, x: usize, y: usize, blocker: Blocker) {
+        if self.in_bounds(x, y) && self.grid[y][x].filled {
+            self.grid[y][x].blocker = blocker;
         }
     }
 
-    // pub fn point_color(&self, grid_x: usize, grid_y: usize) -> &'static str {
-    //     self.grid[grid_y][grid_x].color
-    // }
+    pub fn jelly(&self, x: usize, y: usize) -> u8 {
+        self.grid[y][x].jelly
+    }
+
+    pub fn set_jelly(&mut self, x: usize, y: usize, layers: u8) {
+        if self.in_bounds(x, y) && self.grid[y][x].filled {
+            self.grid[y][x].jelly = layers;
+        }
+    }
+
+    // how many layers of jelly are left on the whole board
+    pub fn jelly_left(&self) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .map(|space| space.jelly as usize)
+            .sum()
+    }
+
+    pub fn ingredients_left(&self) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .filter(|space| space.color == CandyColor::Ingredient)
+            .count()
+    }
+
+    // take every ingredient that has reached the bottom of its column off the board, leaving a
+    // blacked out space for the candies above to fall into. Returns how many were taken
+    pub fn collect_ingredients(&mut self) -> usize {
+        let mut collected = 0;
+        for col in 0..self.width {
+            // the bottom of the column is its lowest space that isn't a hole
+            let Some(bottom) = (0..self.height)
+                .rev()
+                .find(|&row| self.grid[row][col].filled)
+            else {
+                continue;
+            };
+            if self.grid[bottom][col].color == CandyColor::Ingredient {
+                self.set_black(col, bottom);
+                collected += 1;
+            }
+        }
+        collected
+    }
 
-    pub fn swap_colors(&mut self, x_coord: f32, y_coord: f32, last_clicked: (f32, f32)) {
-        let (last_x, last_y) = last_clicked;
+    pub fn swap_colors(&mut self, first: (usize, usize), second: (usize, usize)) {
+        let (x1, y1) = first;
+        let (x2, y2) = second;
 
-        let color1 = self.grid[y_coord as usize][x_coord as usize].color;
-        let color2 = self.grid[last_y as usize][last_x as usize].color;
-        // swap the colors
-        self.grid[y_coord as usize][x_coord as usize].color = color2;
-        self.grid[last_y as usize][last_x as usize].color = color1;
+        let (color1, special1) = (self.grid[y1][x1].color, self.grid[y1][x1].special);
+        let (color2, special2) = (self.grid[y2][x2].color, self.grid[y2][x2].special);
+        // swap the colors, and the special candies along with them
+        self.grid[y1][x1].color = color2;
+        self.grid[y1][x1].special = special2;
+        self.grid[y2][x2].color = color1;
+        self.grid[y2][x2].special = special1;
     }
 
-    pub fn get_color_coords(&self, sprite_col: usize, sprite_row: usize) -> [f32; 4] {
-        let color = match self.grid[sprite_col][sprite_row].color {
-            "white" => [0.0, 0.0, 8.0 / 80.0, 8.0 / 160.0],
-            "dark blue" => [0.0, 16.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            "light blue" => [0.0 / 23.0, 32.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            "light orange" => [0.0 / 80.0, 48.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            "dark orange" => [0.0 / 80.0, 64.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            "white orange" => [0.0 / 80.0, 80.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            "black" => [0.0 / 80.0, 96.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-            // Add cases for other colors
-            _ => [0.0, 0.0, 8.0 / 80.0, 8.0 / 160.0], // Default to a color
+    pub fn get_color_coords(&self, x: usize, y: usize) -> [f32; 4] {
+        self.grid[y][x].sheet_region()
+    }
+
+    // the region of the sprite sheet for the blocker drawn over a space's candy
+    pub fn get_blocker_coords(&self, x: usize, y: usize) -> [f32; 4] {
+        self.grid[y][x].blocker.sheet_region()
+    }
+
+    // the region of the sprite sheet for the jelly drawn over a space. The frame gets thicker
+    // with more layers, and spaces without jelly draw nothing
+    pub fn get_jelly_coords(&self, x: usize, y: usize) -> [f32; 4] {
+        match self.grid[y][x].jelly {
+            0 => CandyColor::Empty.sheet_region(),
+            1 => [48.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+            _ => [64.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+        }
+    }
+
+    // whether two spaces are allowed to be swapped at all: they have to be next to each other
+    // and both hold a candy that isn't covered by a blocker
+    pub fn can_swap(&self, first: (usize, usize), second: (usize, usize)) -> bool {
+        let (x1, y1) = first;
+        let (x2, y2) = second;
+        let holds_candy = |x: usize, y: usize| {
+            self.in_bounds(x, y)
+                && self.grid[y][x].filled
+                && self.color(x, y).is_candy()
+                && self.grid[y][x].blocker == Blocker::None
         };
-        color
+        holds_candy(x1, y1) && holds_candy(x2, y2) && x1.abs_diff(x2) + y1.abs_diff(y2) == 1
     }
 
-    pub fn color_is_black(&self, x: usize, y: usize) -> bool {
-        if x < 10 && y < 20 {
-            let color = self.grid[y][x].color;
-            return color == "black";
-        } else {
-            false
+    // whether swapping two spaces would make a match or set off a color bomb, worked out
+    // without touching the board
+    pub fn swap_makes_match(&self, first: (usize, usize), second: (usize, usize)) -> bool {
+        if self.special(first.0, first.1) == Special::ColorBomb
+            || self.special(second.0, second.1) == Special::ColorBomb
+        {
+            return true;
+        }
+
+        // the color that could match at a space once the swap is made
+        let color_at = |x: usize, y: usize| {
+            let (x, y) = if (x, y) == first {
+                second
+            } else if (x, y) == second {
+                first
+            } else {
+                (x, y)
+            };
+            let space = self.grid[y][x];
+            space.is_matchable().then_some(space.color)
+        };
+
+        // only the rows and columns through the swapped spaces can have changed
+        for (x, y) in [first, second] {
+            let Some(color) = color_at(x, y) else {
+                continue;
+            };
+            let same = |(cx, cy): (usize, usize)| color_at(cx, cy) == Some(color);
+            let left = (0..x).rev().take_while(|&cx| same((cx, y))).count();
+            let right = (x + 1..self.width).take_while(|&cx| same((cx, y))).count();
+            let up = (0..y).rev().take_while(|&cy| same((x, cy))).count();
+            let down = (y + 1..self.height).take_while(|&cy| same((x, cy))).count();
+            if left + right + 1 >= 3 || up + down + 1 >= 3 {
+                return true;
+            }
+        }
+        false
+    }
+
+    // swap two spaces, but only keep the swap if it makes a match or sets off a color bomb.
+    // Returns whether the swap was kept; if not, the board is left as it was
+    pub fn try_swap(&mut self, first: (usize, usize), second: (usize, usize)) -> bool {
+        if !self.can_swap(first, second) || !self.swap_makes_match(first, second) {
+            return false;
+        }
+        self.swap_colors(first, second);
+        true
+    }
+
+    // every swap the player could make right now that would make a match
+    pub fn find_valid_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                // checking only the right and lower neighbors finds every pair once
+                for second in [(col + 1, row), (col, row + 1)] {
+                    if self.can_swap((col, row), second)
+                        && self.swap_makes_match((col, row), second)
+                    {
+                        moves.push(Move {
+                            first: (col, row),
+                            second,
+                        });
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    // how many spaces a move would black out straight away, not counting any chain matches
+    // from the candies that fall afterwards
+    pub fn move_value(&self, possible: Move) -> usize {
+        let mut trial = self.clone();
+        if !trial.try_swap(possible.first, possible.second) {
+            return 0;
+        }
+        match trial.detonate_swap(possible.first, possible.second) {
+            Some(clear) => clear.cleared,
+            None => {
+                trial
+                    .clear_matches(&[possible.first, possible.second])
+                    .cleared
+            }
+        }
+    }
+
+    // the valid move that blacks out the most spaces, if there are any moves
+    pub fn best_move(&self) -> Option<Move> {
+        self.find_valid_moves()
+            .into_iter()
+            .max_by_key(|&possible| self.move_value(possible))
+    }
+
+    // pick up every candy on the board, specials included, and put them back down in the order
+    // left by `arrange`, which is handed the candies in board order
+    pub fn rearrange_candies(&mut self, arrange: impl FnOnce(&mut Vec<(CandyColor, Special)>)) {
+        let spaces: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| (col, row)))
+            .filter(|&(col, row)| {
+                let space = self.grid[row][col];
+                // ingredients stay put, since shuffling could carry them back up the board, and
+                // so do covered candies
+                space.filled
+                    && space.color.is_candy()
+                    && space.color != CandyColor::Ingredient
+                    && space.blocker == Blocker::None
+            })
+            .collect();
+        let mut candies: Vec<(CandyColor, Special)> = spaces
+            .iter()
+            .map(|&(col, row)| (self.grid[row][col].color, self.grid[row][col].special))
+            .collect();
+
+        arrange(&mut candies);
+
+        for ((col, row), (color, special)) in spaces.into_iter().zip(candies) {
+            self.grid[row][col].color = color;
+            self.grid[row][col].special = special;
         }
     }
 
     pub fn set_black(&mut self, x: usize, y: usize) {
-        let color = "black";
-        self.grid[x][y].color = color;
-        // println!("color after {} for ({}, {})", self.grid[x][y].color, x, y);
-    }
-
-    pub fn check_blackout_horiz(&self) -> (usize, usize, usize) {
-        for row in 0..20 {
-            let mut consecutive_count = 1;
-            let mut last_color = "black";
-
-            for index in 0..10 {
-                let space = &self.grid[row][index];
-                // println!("{last_color}");
-                if space.filled && space.color == last_color && last_color != "black" {
-                    consecutive_count += 1;
-                    if consecutive_count == 4 {
-                        // println!("row{row} index{index}");
-                        if index < 4 {
-                            return (row + 1, row, index);
-                        } else {
-                            return (((index - 3) * 20) + row + 1, row, index - 3);
-                        }
+        // holes have nothing in them to black out
+        if !self.grid[y][x].filled {
+            return;
+        }
+        self.grid[y][x].color = CandyColor::Cleared;
+        self.grid[y][x].special = Special::None;
+        // println!("color after {:?} for ({}, {})", self.grid[y][x].color, x, y);
+    }
+
+    // every straight run of three or more matching candies along rows or along columns
+    fn find_runs(&self, orientation: Orientation) -> Vec<Match> {
+        let horizontal = orientation == Orientation::Horizontal;
+        let (lines, line_length) = if horizontal {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        // the (x, y) of the space at a position along a line
+        let at = |line: usize, index: usize| {
+            if horizontal {
+                (index, line)
+            } else {
+                (line, index)
+            }
+        };
+
+        let mut runs = Vec::new();
+        for line in 0..lines {
+            let mut start = 0;
+            while start < line_length {
+                let (x, y) = at(line, start);
+                let space = self.grid[y][x];
+
+                // find where the run of this color ends
+                let mut end = start + 1;
+                while end < line_length {
+                    let (x, y) = at(line, end);
+                    let next = self.grid[y][x];
+                    if !next.is_matchable() || next.color != space.color {
+                        break;
                     }
-                } else {
-                    consecutive_count = 1;
-                    last_color = space.color;
+                    end += 1;
+                }
+
+                if space.is_matchable() && end - start >= 3 {
+                    runs.push(Match {
+                        color: space.color,
+                        orientation,
+                        length: end - start,
+                        cells: (start..end).map(|index| at(line, index)).collect(),
+                    });
                 }
+                start = end;
             }
         }
-        // return 202 to signify that there are not 4 in a row
-        return (202, 0, 0);
+        runs
     }
-    pub fn check_blackout_vert(&self) -> (usize, usize, usize) {
-        // Check vertically
-        for col in 0..10 {
-            let mut consecutive_count = 1;
-            let mut last_color = "black";
 
-            for row in 0..20 {
-                let space = &self.grid[row][col];
+    // every match on the board. Runs of the same color that share a space are merged into
+    // one match, so L, T and + shapes come back as a single Cross match
+    pub fn find_matches(&self) -> Vec<Match> {
+        let mut runs = self.find_runs(Orientation::Horizontal);
+        runs.extend(self.find_runs(Orientation::Vertical));
+
+        let mut matches: Vec<Match> = Vec::new();
+        for run in runs {
+            // fold every match this run touches into it
+            let mut merged = run;
+            let mut index = 0;
+            while index < matches.len() {
+                if matches[index].color == merged.color && matches[index].overlaps(&merged) {
+                    merged = merged.merge(matches.swap_remove(index));
+                } else {
+                    index += 1;
+                }
+            }
+            matches.push(merged);
+        }
+
+        // report matches from the top left down, the same order the board is read in
+        matches.sort_by_key(|found| {
+            let (x, y) = found.cells[0];
+            (y, x)
+        });
+        matches
+    }
 
-                if space.filled && space.color == last_color && last_color != "black" {
-                    consecutive_count += 1;
-                    if consecutive_count == 4 {
-                        // return the first index of the four in a column
-                        return ((row - 2) + (20 * col), row - 3, col);
+    // the spaces a special candy clears when it goes off
+    fn blast_area(&self, x: usize, y: usize, special: Special) -> Vec<(usize, usize)> {
+        match special {
+            Special::None => Vec::new(),
+            Special::StripedHorizontal => (0..self.width).map(|col| (col, y)).collect(),
+            Special::StripedVertical => (0..self.height).map(|row| (x, row)).collect(),
+            Special::Wrapped => {
+                let mut area = Vec::new();
+                for row in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
+                    for col in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
+                        area.push((col, row));
                     }
+                }
+                area
+            }
+            Special::ColorBomb => self.spaces_of_color(self.grid[y][x].color),
+        }
+    }
+
+    // the spaces directly above, below, left and right of a space that are on the board
+    pub fn neighbors(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(|&(nx, ny)| self.in_bounds(nx, ny))
+        .collect()
+    }
+
+    fn spaces_of_color(&self, color: CandyColor) -> Vec<(usize, usize)> {
+        let mut spaces = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if self.grid[row][col].color == color {
+                    spaces.push((col, row));
+                }
+            }
+        }
+        spaces
+    }
+
+    // black out the given spaces, setting off any special candies among them, which can set off
+    // more in turn. Spaces in `keep` are left alone, and locked candies only lose their lock.
+    // `detonated` is whether the given spaces were themselves hit by a detonation
+    fn blast(
+        &mut self,
+        spaces: Vec<(usize, usize)>,
+        keep: &[(usize, usize)],
+        detonated: bool,
+        clear: &mut Clear,
+    ) {
+        let mut hit = vec![vec![false; self.width]; self.height];
+        // the spaces hit without a detonation reaching them
+        let mut direct = vec![vec![false; self.width]; self.height];
+        if !detonated {
+            for &(x, y) in &spaces {
+                direct[y][x] = true;
+            }
+        }
+        let mut queue = spaces;
+        while let Some((x, y)) = queue.pop() {
+            let space = self.grid[y][x];
+            let breakable = (space.color.is_candy() && space.color != CandyColor::Ingredient)
+                || space.color == CandyColor::Chocolate;
+            if hit[y][x] || keep.contains(&(x, y)) || !space.filled || !breakable {
+                continue;
+            }
+            hit[y][x] = true;
+            if space.special != Special::None && space.blocker != Blocker::Lock {
+                clear.detonations.push(((x, y), space.special));
+                queue.extend(self.blast_area(x, y, space.special));
+            }
+        }
+
+        for (row, columns) in hit.iter().enumerate() {
+            for (col, &was_hit) in columns.iter().enumerate() {
+                if !was_hit {
+                    continue;
+                }
+                if self.grid[row][col].blocker == Blocker::Lock {
+                    self.grid[row][col].blocker = Blocker::None;
+                    clear.unlocked += 1;
                 } else {
-                    consecutive_count = 1;
-                    last_color = space.color;
+                    self.black_out(col, row, clear);
+                    if !direct[row][col] {
+                        clear.blasted += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // black out one space, taking a layer of jelly and any ice off with it, and record it in `clear`
+    fn black_out(&mut self, x: usize, y: usize, clear: &mut Clear) {
+        clear.colors.push(self.grid[y][x].color);
+        clear.cleared += 1;
+        if self.grid[y][x].color == CandyColor::Chocolate {
+            clear.chocolate += 1;
+        }
+        self.grid[y][x].blocker = Blocker::None;
+        if self.grid[y][x].jelly > 0 {
+            self.grid[y][x].jelly -= 1;
+            clear.jelly += 1;
+        }
+        self.set_black(x, y);
+    }
+
+    // where a match leaves its special candy: the space the player moved into the match if
+    // there is one, otherwise the corner of a cross or the middle of a line
+    fn special_spot(&self, found: &Match, swapped: &[(usize, usize)]) -> Option<(usize, usize)> {
+        let plain = |cell: &&(usize, usize)| {
+            let space = self.grid[cell.1][cell.0];
+            space.special == Special::None && space.blocker == Blocker::None
+        };
+        if let Some(&cell) = swapped
+            .iter()
+            .filter(|cell| found.cells.contains(cell))
+            .find(plain)
+        {
+            return Some(cell);
+        }
+        if found.orientation == Orientation::Cross {
+            // the corner is the one space with a neighbor in the match both across and down
+            let corner = found.cells.iter().filter(plain).find(|&&(x, y)| {
+                let across = found
+                    .cells
+                    .iter()
+                    .any(|&(cx, cy)| cy == y && cx.abs_diff(x) == 1);
+                let down = found
+                    .cells
+                    .iter()
+                    .any(|&(cx, cy)| cx == x && cy.abs_diff(y) == 1);
+                across && down
+            });
+            if corner.is_some() {
+                return corner.copied();
+            }
+        }
+        let middle = found.cells.len() / 2;
+        found.cells[middle..]
+            .iter()
+            .chain(found.cells[..middle].iter())
+            .find(plain)
+            .copied()
+    }
+
+    // black out every match on the board, making special candies from the big ones and setting
+    // off any special candies that were matched. `swapped` is the pair of spaces the player just
+    // swapped, which decides where new special candies go
+    pub fn clear_matches(&mut self, swapped: &[(usize, usize)]) -> Clear {
+        let mut clear = Clear {
+            matches: self.find_matches(),
+            ..Clear::default()
+        };
+
+        for found in &clear.matches {
+            let special = Special::made_by(found);
+            if special == Special::None {
+                continue;
+            }
+            if let Some(spot) = self.special_spot(found, swapped) {
+                clear.created.push((spot, special));
+            }
+        }
+
+        let spots: Vec<(usize, usize)> = clear.created.iter().map(|&(spot, _)| spot).collect();
+        let mut matched: Vec<(usize, usize)> = clear
+            .matches
+            .iter()
+            .flat_map(|found| found.cells.iter().copied())
+            .collect();
+        // a match breaks any chocolate next to it
+        let chocolate: Vec<(usize, usize)> = matched
+            .iter()
+            .flat_map(|&(x, y)| self.neighbors(x, y))
+            .filter(|&(x, y)| self.color(x, y) == CandyColor::Chocolate)
+            .collect();
+        matched.extend(chocolate);
+        self.blast(matched, &spots, false, &mut clear);
+
+        for &((x, y), special) in &clear.created {
+            self.grid[y][x].special = special;
+        }
+        clear
+    }
+
+    // set off a color bomb that was swapped with the space next to it. The bomb clears every candy
+    // of the other space's color, or the whole board if both are bombs. Returns None if neither
+    // space holds a color bomb
+    pub fn detonate_swap(
+        &mut self,
+        first: (usize, usize),
+        second: (usize, usize),
+    ) -> Option<Clear> {
+        let (bomb, other) = if self.special(first.0, first.1) == Special::ColorBomb {
+            (first, second)
+        } else if self.special(second.0, second.1) == Special::ColorBomb {
+            (second, first)
+        } else {
+            return None;
+        };
+
+        let mut clear = Clear::default();
+        clear.detonations.push((bomb, Special::ColorBomb));
+        let targets = if self.special(other.0, other.1) == Special::ColorBomb {
+            (0..self.height)
+                .flat_map(|row| (0..self.width).map(move |col| (col, row)))
+                .collect()
+        } else {
+            self.spaces_of_color(self.color(other.0, other.1))
+        };
+
+        // the bomb has been used up, so it is blacked out without going off again
+        self.black_out(bomb.0, bomb.1, &mut clear);
+        self.blast(targets, &[], true, &mut clear);
+        Some(clear)
+    }
+
+    // let candies fall into the blacked out spaces below them, leaving empty spaces at the top of each column.
+    // Candies can't fall past stone, chocolate or a covered candy, so the spaces under one of those
+    // are left empty at the top of their own stretch of the column. Holes are fallen straight through
+    pub fn collapse(&mut self) {
+        for col in 0..self.width {
+            // the lowest space that hasn't been given a candy yet
+            let mut landing_row = self.height;
+            for row in (0..self.height).rev() {
+                let space = self.grid[row][col];
+                if !space.filled {
+                    continue;
+                }
+                if space.is_fixed() {
+                    self.empty_spaces(col, row + 1..landing_row);
+                    landing_row = row;
+                } else if space.color.is_candy() {
+                    // the next space up that isn't a hole, which at the highest is this one
+                    landing_row = (row..landing_row)
+                        .rev()
+                        .find(|&landing| self.grid[landing][col].filled)
+                        .unwrap();
+                    self.grid[landing_row][col].color = space.color;
+                    self.grid[landing_row][col].special = space.special;
                 }
             }
+            self.empty_spaces(col, 0..landing_row);
+        }
+    }
+
+    fn empty_spaces(&mut self, col: usize, rows: std::ops::Range<usize>) {
+        for row in rows {
+            if self.grid[row][col].filled {
+                self.grid[row][col].color = CandyColor::Empty;
+                self.grid[row][col].special = Special::None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a board of stone except for the given candies, so nothing else can match, fall or be
+    // blasted
+    fn board_with(candies: &[((usize, usize), CandyColor)]) -> GameGrid {
+        let mut game_grid = GameGrid::with_size(5, 5);
+        for row in 0..5 {
+            for col in 0..5 {
+                game_grid.fill_space(col, row, CandyColor::Stone);
+            }
         }
-        // return 202 to signify that there are not 4 in a column
-        return (202, 0, 0); // No four consecutive spaces found
+        for &((x, y), color) in candies {
+            game_grid.fill_space(x, y, color);
+        }
+        game_grid
+    }
+
+    fn make_special(game_grid: &mut GameGrid, (x, y): (usize, usize), special: Special) {
+        game_grid.grid[y][x].special = special;
+    }
+
+    #[test]
+    fn locked_candies_stay_put_until_matched() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[((0, 4), White), ((1, 4), White), ((2, 4), White)]);
+        game_grid.set_blocker(1, 4, Blocker::Lock);
+        game_grid.fill_space(1, 3, DarkBlue);
+        assert!(!game_grid.can_swap((1, 4), (1, 3)));
+
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.cleared, 2);
+        assert_eq!(clear.unlocked, 1);
+        assert_eq!(game_grid.color(1, 4), White);
+        assert_eq!(game_grid.blocker(1, 4), Blocker::None);
+    }
+
+    #[test]
+    fn ice_holds_its_candy_up_and_breaks_with_it() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[((0, 2), White), ((0, 3), DarkBlue), ((0, 4), White)]);
+        game_grid.set_blocker(0, 3, Blocker::Ice);
+        game_grid.set_black(0, 4);
+        game_grid.collapse();
+        // the frozen candy didn't fall into the gap below it, and held up the one above
+        assert_eq!(game_grid.color(0, 3), DarkBlue);
+        assert_eq!(game_grid.color(0, 2), White);
+        assert_eq!(game_grid.color(0, 4), Empty);
+
+        let mut game_grid = board_with(&[((0, 4), White), ((1, 4), White), ((2, 4), White)]);
+        game_grid.set_blocker(0, 4, Blocker::Ice);
+        assert_eq!(game_grid.clear_matches(&[]).cleared, 3);
+        assert_eq!(game_grid.blocker(0, 4), Blocker::None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn candies_fall_through_holes() {
+        use CandyColor::*;
+        let mut game_grid = GameGrid::with_size(1, 5);
+        game_grid.fill_space(0, 0, White);
+        game_grid.make_hole(0, 2);
+        game_grid.fill_space(0, 2, DarkBlue);
+        assert_eq!(game_grid.color(0, 2), Empty);
+
+        game_grid.collapse();
+        assert_eq!(game_grid.color(0, 4), White);
+        assert_eq!(game_grid.color(0, 0), Empty);
+        assert!(!game_grid.is_filled(0, 2));
+
+        // an ingredient stops at the lowest space in its column, above the hole
+        let mut game_grid = GameGrid::with_size(1, 3);
+        game_grid.make_hole(0, 2);
+        game_grid.fill_space(0, 0, Ingredient);
+        game_grid.collapse();
+        assert_eq!(game_grid.color(0, 1), Ingredient);
+        assert_eq!(game_grid.collect_ingredients(), 1);
+    }
+
+    #[test]
+    fn matches_break_chocolate_next_to_them_but_not_stone() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), White),
+            ((3, 4), Chocolate),
+        ]);
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.chocolate, 1);
+        assert_eq!(game_grid.color(3, 4), Cleared);
+        assert_eq!(game_grid.color(0, 3), Stone);
+    }
+
+    #[test]
+    fn runs_sharing_a_space_merge_into_one_cross() {
+        use CandyColor::*;
+        let l_shape = [(0, 2), (0, 3), (0, 4), (1, 4), (2, 4)];
+        let t_shape = [(0, 0), (1, 0), (2, 0), (1, 1), (1, 2)];
+        let plus = [(2, 1), (1, 2), (2, 2), (3, 2), (2, 3)];
+        for shape in [l_shape, t_shape, plus] {
+            let candies: Vec<_> = shape.iter().map(|&cell| (cell, White)).collect();
+            let matches = board_with(&candies).find_matches();
+            assert_eq!(matches.len(), 1, "{:?} came back in pieces", shape);
+            assert_eq!(matches[0].orientation, Orientation::Cross);
+            assert_eq!(matches[0].length, 3);
+            let mut cells = shape.to_vec();
+            cells.sort_by_key(|&(x, y)| (y, x));
+            assert_eq!(matches[0].cells, cells);
+        }
+    }
+
+    #[test]
+    fn one_swap_can_make_a_row_and_a_column_match() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), DarkBlue),
+            ((2, 3), White),
+            ((2, 2), DarkBlue),
+            ((2, 1), DarkBlue),
+        ]);
+        game_grid.swap_colors((2, 4), (2, 3));
+
+        let clear = game_grid.clear_matches(&[(2, 4), (2, 3)]);
+        let orientations: Vec<_> = clear
+            .matches
+            .iter()
+            .map(|found| (found.color, found.orientation))
+            .collect();
+        assert_eq!(
+            orientations,
+            [
+                (DarkBlue, Orientation::Vertical),
+                (White, Orientation::Horizontal)
+            ]
+        );
+        assert_eq!(clear.cleared, 6);
+    }
+
+    #[test]
+    fn long_runs_report_their_length() {
+        use CandyColor::*;
+        let mut candies: Vec<_> = (0..5).map(|x| ((x, 0), White)).collect();
+        candies.extend((1..5).map(|y| ((4, y), DarkBlue)));
+        let matches = board_with(&candies).find_matches();
+        let lengths: Vec<_> = matches
+            .iter()
+            .map(|found| (found.orientation, found.length, found.cells.len()))
+            .collect();
+        assert_eq!(
+            lengths,
+            [
+                (Orientation::Horizontal, 5, 5),
+                (Orientation::Vertical, 4, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_of_the_same_color_only_merge_when_they_share_a_space() {
+        use CandyColor::*;
+        // two rows side by side touch all along, but share no space
+        let candies: Vec<_> = (0..3)
+            .flat_map(|x| [((x, 3), White), ((x, 4), White)])
+            .collect();
+        let matches = board_with(&candies).find_matches();
+        assert_eq!(matches.len(), 2);
+        assert!(matches
+            .iter()
+            .all(|found| found.orientation == Orientation::Horizontal && found.length == 3));
+    }
+
+    #[test]
+    fn big_matches_leave_a_special_candy_behind() {
+        use CandyColor::*;
+        // the candy the player moved in gets the special
+        let mut game_grid = board_with(&(0..4).map(|x| ((x, 4), White)).collect::<Vec<_>>());
+        let clear = game_grid.clear_matches(&[(1, 4), (1, 3)]);
+        assert_eq!(clear.created, [((1, 4), Special::StripedVertical)]);
+        assert_eq!(game_grid.color(1, 4), White);
+        assert_eq!(game_grid.special(1, 4), Special::StripedVertical);
+        assert_eq!(clear.cleared, 3);
+
+        // with no swap, a line puts it in the middle
+        let mut game_grid = board_with(&(1..5).map(|y| ((0, y), White)).collect::<Vec<_>>());
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.created, [((0, 3), Special::StripedHorizontal)]);
+
+        let mut game_grid = board_with(&(0..5).map(|x| ((x, 2), White)).collect::<Vec<_>>());
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.created, [((2, 2), Special::ColorBomb)]);
+
+        // and a cross in its corner
+        let mut game_grid = board_with(&[
+            ((0, 2), White),
+            ((0, 3), White),
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), White),
+        ]);
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.created, [((0, 4), Special::Wrapped)]);
+
+        // a plain three makes nothing
+        let mut game_grid = board_with(&(0..3).map(|x| ((x, 0), White)).collect::<Vec<_>>());
+        assert!(game_grid.clear_matches(&[]).created.is_empty());
+    }
+
+    #[test]
+    fn matched_striped_candies_set_each_other_off() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[
+            ((2, 2), White),
+            ((2, 3), White),
+            ((2, 4), White),
+            ((0, 2), DarkBlue),
+            ((1, 2), LightBlue),
+            ((3, 2), LightOrange),
+            ((4, 2), DarkOrange),
+            ((4, 0), DarkBlue),
+            ((4, 4), LightBlue),
+            ((3, 0), LightOrange),
+        ]);
+        make_special(&mut game_grid, (2, 2), Special::StripedHorizontal);
+        // caught in the first one's row, and clears its own column
+        make_special(&mut game_grid, (4, 2), Special::StripedVertical);
+
+        let clear = game_grid.clear_matches(&[]);
+        let mut detonations = clear.detonations.clone();
+        detonations.sort_by_key(|&((x, y), _)| (y, x));
+        assert_eq!(
+            detonations,
+            [
+                ((2, 2), Special::StripedHorizontal),
+                ((4, 2), Special::StripedVertical)
+            ]
+        );
+        assert_eq!(clear.cleared, 9);
+        assert!((0..5).all(|x| game_grid.color(x, 2) == Cleared));
+        assert_eq!(game_grid.color(4, 0), Cleared);
+        assert_eq!(game_grid.color(4, 4), Cleared);
+        // nothing outside the row and column is touched
+        assert_eq!(game_grid.color(3, 0), LightOrange);
+        // stone stands up to blasts
+        assert_eq!(game_grid.color(4, 1), Stone);
+    }
+
+    #[test]
+    fn wrapped_candies_clear_the_square_around_them() {
+        use CandyColor::*;
+        let mut game_grid = board_with(&[
+            ((0, 0), DarkBlue),
+            ((1, 0), LightBlue),
+            ((2, 0), DarkBlue),
+            ((0, 1), White),
+            ((1, 1), White),
+            ((2, 1), White),
+            ((0, 2), LightBlue),
+            ((1, 2), DarkBlue),
+            ((2, 2), LightBlue),
+            ((3, 1), DarkOrange),
+        ]);
+        make_special(&mut game_grid, (1, 1), Special::Wrapped);
+
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!(clear.detonations, [((1, 1), Special::Wrapped)]);
+        assert_eq!(clear.cleared, 9);
+        assert!((0..3).all(|y| (0..3).all(|x| game_grid.color(x, y) == Cleared)));
+        assert_eq!(game_grid.color(3, 1), DarkOrange);
+    }
+
+    #[test]
+    fn color_bombs_clear_the_color_they_are_swapped_with() {
+        use CandyColor::*;
+        let candies = [
+            ((0, 0), White),
+            ((1, 0), DarkBlue),
+            ((2, 2), White),
+            ((4, 4), White),
+            ((3, 3), LightBlue),
+        ];
+        let mut game_grid = board_with(&candies);
+        assert!(game_grid.detonate_swap((0, 0), (1, 0)).is_none());
+        make_special(&mut game_grid, (1, 0), Special::ColorBomb);
+
+        let clear = game_grid.detonate_swap((1, 0), (0, 0)).unwrap();
+        assert_eq!(clear.detonations, [((1, 0), Special::ColorBomb)]);
+        // the bomb and every white candy
+        assert_eq!(clear.cleared, 4);
+        assert_eq!(game_grid.color(2, 2), Cleared);
+        assert_eq!(game_grid.color(3, 3), LightBlue);
+
+        // two bombs clear every candy on the board
+        let mut game_grid = board_with(&candies);
+        make_special(&mut game_grid, (0, 0), Special::ColorBomb);
+        make_special(&mut game_grid, (1, 0), Special::ColorBomb);
+        let clear = game_grid.detonate_swap((0, 0), (1, 0)).unwrap();
+        assert_eq!(clear.cleared, candies.len());
+        assert_eq!(game_grid.color(3, 3), Cleared);
+        assert_eq!(game_grid.color(0, 4), Stone);
+    }
+
+    #[test]
+    fn the_best_move_clears_the_most() {
+        use CandyColor::*;
+        let game_grid = board_with(&[
+            // a column of three
+            ((4, 0), LightBlue),
+            ((4, 1), LightBlue),
+            ((4, 2), LightOrange),
+            ((3, 2), LightBlue),
+            // a row and a column of three at once
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), DarkBlue),
+            ((2, 3), White),
+            ((2, 2), DarkBlue),
+            ((2, 1), DarkBlue),
+        ]);
+        let column = Move {
+            first: (3, 2),
+            second: (4, 2),
+        };
+        let both = Move {
+            first: (2, 3),
+            second: (2, 4),
+        };
+        assert_eq!(game_grid.find_valid_moves(), [column, both]);
+        assert_eq!(game_grid.move_value(column), 3);
+        assert_eq!(game_grid.move_value(both), 6);
+        assert_eq!(game_grid.best_move(), Some(both));
+    }
+
+    #[test]
+    fn only_spaces_a_detonation_reaches_count_as_blasted() {
+        use CandyColor::*;
+        // chocolate broken by a plain match next to it wasn't blasted
+        let mut game_grid = board_with(&[
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), White),
+            ((3, 4), Chocolate),
+        ]);
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!((clear.cleared, clear.blasted), (4, 0));
+
+        // a locked candy in the match only loses its lock, while the striped candy next to it
+        // blasts the rest of its column
+        let mut game_grid = board_with(&[
+            ((0, 4), White),
+            ((1, 4), White),
+            ((2, 4), White),
+            ((1, 3), DarkBlue),
+            ((1, 2), LightBlue),
+        ]);
+        game_grid.set_blocker(0, 4, Blocker::Lock);
+        make_special(&mut game_grid, (1, 4), Special::StripedVertical);
+        let clear = game_grid.clear_matches(&[]);
+        assert_eq!((clear.cleared, clear.unlocked, clear.blasted), (4, 1, 2));
+
+        // a swapped color bomb blasts every candy of the other color
+        let mut game_grid = board_with(&[((0, 0), White), ((1, 0), DarkBlue), ((3, 3), White)]);
+        make_special(&mut game_grid, (1, 0), Special::ColorBomb);
+        let clear = game_grid.detonate_swap((1, 0), (0, 0)).unwrap();
+        assert_eq!((clear.cleared, clear.blasted), (3, 2));
+    }
+}
@@ -1,106 +1,316 @@
-use rand::Rng;
-use crate::grid::{GameGrid, screen_to_grid};
-use crate::GPUSprite;
-
-
-
-pub fn generate_grid(mut x: f32, mut y: f32, game_grid: &mut GameGrid, sprites: &mut Vec<GPUSprite>) {
-    let mut color_counters = [0; 6];
-    // loop through every slot in the grid
-    for _col in 0..10 {
-        for _row in 0..20 {
-            // generate a random number between 0 and 2. This number will be used to select the color of the sprite
-            let mut rng = rand::thread_rng();
-            let mut random_number: u32 = rng.gen_range(0..=5);
-            let mut sprite: GPUSprite = GPUSprite {
-                screen_region: [0.0, 0.0, 8.0, 8.0],
-                sheet_region: [0.0 / 80.0, 0.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
+use crate::grid::{Blocker, CandyColor, GameGrid};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// every random choice the game makes comes from one of these, so a seed always gives the
+// same board and the same refills. It is the generator behind rand's StdRng, named so its state
+// can be read out and saved part way through a game
+pub type GameRng = rand_chacha::ChaCha12Rng;
+
+pub fn seeded_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+// pick a random color out of `colors`, choosing again if it would make more than three of the same
+// color in a row. `run` is the color and length of the run the new candy would extend
+fn pick_color(
+    rng: &mut impl Rng,
+    colors: &[CandyColor],
+    run: Option<(CandyColor, usize)>,
+) -> CandyColor {
+    // pick a random color for the space out of the ones the board allows
+    let mut color = colors[rng.gen_range(0..colors.len())];
+
+    // Check if placing more than three of the same color in a row
+    if let Some((run_color, run_length)) = run {
+        if run_color == color && run_length >= 3 {
+            // If more than three, select a different color
+            while color == run_color {
+                color = colors[rng.gen_range(0..colors.len())];
+            }
+        }
+    }
+    color
+}
+
+// how many valid moves a new board starts with at the least
+pub const MIN_STARTING_MOVES: usize = 3;
+// how many boards generate_grid tries before giving up
+const GENERATE_ATTEMPTS: usize = 1000;
+
+// pick a random color for a space being generated that doesn't finish a line of three with the
+// two spaces to its left or the two above it, which are the neighbors already filled in
+fn pick_unmatched_color(
+    game_grid: &GameGrid,
+    rng: &mut impl Rng,
+    x: usize,
+    y: usize,
+) -> CandyColor {
+    let lines_up = |first: (usize, usize), second: (usize, usize)| {
+        let color = game_grid.color(first.0, first.1);
+        (color == game_grid.color(second.0, second.1)).then_some(color)
+    };
+    let left = if x >= 2 {
+        lines_up((x - 1, y), (x - 2, y))
+    } else {
+        None
+    };
+    let above = if y >= 2 {
+        lines_up((x, y - 1), (x, y - 2))
+    } else {
+        None
+    };
+
+    // at most two colors are ruled out, and boards have at least three, so there is always one left
+    let allowed: Vec<CandyColor> = game_grid
+        .colors()
+        .iter()
+        .copied()
+        .filter(|&color| Some(color) != left && Some(color) != above)
+        .collect();
+    *allowed.choose(rng).unwrap()
+}
+
+// fill every empty space of the grid with a candy so that nothing on the board matches yet and
+// the player has at least `min_moves` valid moves. Spaces a level has already placed something in
+// are left as they are. Returns false if no such board was found, in which case the grid is left
+// with the last board tried
+pub fn generate_grid(game_grid: &mut GameGrid, rng: &mut impl Rng, min_moves: usize) -> bool {
+    let open: Vec<(usize, usize)> = (0..game_grid.width())
+        .flat_map(|col| (0..game_grid.height()).map(move |row| (col, row)))
+        .filter(|&(col, row)| {
+            game_grid.is_filled(col, row) && game_grid.color(col, row) == CandyColor::Empty
+        })
+        .collect();
+
+    for _ in 0..GENERATE_ATTEMPTS {
+        // loop through every open slot in the grid
+        for &(col, row) in &open {
+            let color = pick_unmatched_color(game_grid, rng, col, row);
+            game_grid.fill_space(col, row, color);
+
+            // game_grid.log_space(col, row);
+        }
+
+        // placed candies can still line up with the ones to their right or below
+        if game_grid.find_matches().is_empty() && game_grid.find_valid_moves().len() >= min_moves {
+            return true;
+        }
+    }
+    false
+}
+
+// give every empty space a new candy. Unlike generate_grid, new candies are free to line up with
+// the ones already on the board, which is what makes chain matches. Empty spaces are at the top of
+// their column after GameGrid::collapse, so each column is filled from the bottom of its gap
+// upwards, as if the new candies dropped in
+pub fn refill_grid(game_grid: &mut GameGrid, rng: &mut impl Rng) {
+    for col in 0..game_grid.width() {
+        for row in (0..game_grid.height()).rev() {
+            if !game_grid.is_filled(col, row) || game_grid.color(col, row) != CandyColor::Empty {
+                continue;
+            }
+
+            // measure the run of candies directly below this space
+            let below = row + 1;
+            let run = if below < game_grid.height() && game_grid.color(col, below).is_candy() {
+                let run_color = game_grid.color(col, below);
+                let run_length = (below..game_grid.height())
+                    .take_while(|&y| game_grid.color(col, y) == run_color)
+                    .count();
+                Some((run_color, run_length))
+            } else {
+                None
             };
 
-            // convert pixels to grid units
-            let (grid_x, grid_y) = screen_to_grid(x, y);
+            let color = pick_color(rng, game_grid.colors(), run);
+            game_grid.fill_space(col, row, color);
+        }
+    }
+}
 
-            // println!("{}", random_number);
+// how many arrangements shuffle_grid tries before giving up
+const SHUFFLE_ATTEMPTS: usize = 1000;
 
-            // Check if placing more than three of the same color in a row
-            if color_counters[random_number as usize] >= 3 {
-                // If more than three, select a different color
-                let mut new_random_number = random_number;
-                while new_random_number == random_number {
-                    new_random_number = rng.gen_range(0..=5);
+// rearrange the candies already on the board into a layout with no matches and at least one move.
+// Returns false if no such layout was found, in which case the board is left on the last attempt
+pub fn shuffle_grid(game_grid: &mut GameGrid, rng: &mut impl Rng) -> bool {
+    for _ in 0..SHUFFLE_ATTEMPTS {
+        game_grid.rearrange_candies(|candies| candies.shuffle(rng));
+        if game_grid.find_matches().is_empty() && !game_grid.find_valid_moves().is_empty() {
+            return true;
+        }
+    }
+    false
+}
+
+// let the chocolate on the board take over one random candy next to it. Ingredients and covered
+// candies are safe. Returns false if the chocolate has nowhere to spread
+pub fn spread_chocolate(game_grid: &mut GameGrid, rng: &mut impl Rng) -> bool {
+    let mut targets = Vec::new();
+    for row in 0..game_grid.height() {
+        for col in 0..game_grid.width() {
+            if game_grid.color(col, row) != CandyColor::Chocolate {
+                continue;
+            }
+            for (x, y) in game_grid.neighbors(col, row) {
+                let color = game_grid.color(x, y);
+                if color.is_candy()
+                    && color != CandyColor::Ingredient
+                    && game_grid.blocker(x, y) == Blocker::None
+                    && !targets.contains(&(x, y))
+                {
+                    targets.push((x, y));
                 }
-                random_number = new_random_number;
             }
+        }
+    }
 
-            // Increment the counter for the chosen color
-            color_counters[random_number as usize] += 1;
+    match targets.choose(rng) {
+        Some(&(x, y)) => {
+            game_grid.fill_space(x, y, CandyColor::Chocolate);
+            true
+        }
+        None => false,
+    }
+}
 
-            // Reset the counters for other colors
-            for i in 0..6 {
-                if i != random_number as usize {
-                    color_counters[i] = 0;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::CANDY_COLORS;
+
+    // the board sizes the game is expected to ship with
+    const SIZES: [(usize, usize); 4] = [(10, 20), (7, 7), (9, 9), (8, 12)];
+
+    #[test]
+    fn generated_boards_have_no_matches() {
+        for (width, height) in SIZES {
+            for seed in 0..50 {
+                let mut game_grid = GameGrid::with_size(width, height);
+                generate_grid(&mut game_grid, &mut seeded_rng(seed), MIN_STARTING_MOVES);
+                assert!(
+                    game_grid.find_matches().is_empty(),
+                    "{}x{} board from seed {} started with a match",
+                    width,
+                    height,
+                    seed
+                );
             }
+        }
+    }
 
-            // set the color of the sprite according to the random number generated
-            match random_number {
-                0 => {
-                    // set the color of the sprite to white
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 0.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "white");
-                }
-                1 => {
-                    // set the color of the sprite to dark blue
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 16.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "dark blue");
-                }
-                2 => {
-                    // set the color of the sprite to light blue
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 32.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "light blue");
-                }
-                3 => {
-                    // set the color of the sprite to light orange
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 48.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "light orange");
-                }
-                4 => {
-                    // set the color of the sprite to dark orange
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 64.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "dark orange");
-                }
-                5 => {
-                    // set the color of the sprite to white with orange
-                    sprite = GPUSprite {
-                        screen_region: [x, y, 8.0, 8.0],
-                        sheet_region: [0.0 / 80.0, 80.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-                    };
-                    game_grid.fill_space(grid_x, grid_y, "white orange");
-                }
-                _ => println!("Random number is out of range"),
+    #[test]
+    fn generated_boards_have_enough_moves() {
+        for (width, height) in SIZES {
+            for min_moves in [1, MIN_STARTING_MOVES, 8] {
+                let mut game_grid = GameGrid::with_size(width, height);
+                assert!(generate_grid(&mut game_grid, &mut seeded_rng(1), min_moves));
+                assert!(game_grid.find_valid_moves().len() >= min_moves);
             }
+        }
+    }
+
+    #[test]
+    fn generate_grid_gives_up_on_impossible_move_counts() {
+        // a 3x3 board can't fit more swaps than it has neighboring pairs
+        let mut game_grid = GameGrid::with_size(3, 3);
+        assert!(!generate_grid(&mut game_grid, &mut seeded_rng(1), 13));
+        assert!(game_grid.find_matches().is_empty());
+    }
 
-            // game_grid.print_space(grid_x, grid_y);
-            sprites.push(sprite);
-            y -= 8.0;
+    #[test]
+    fn same_seed_gives_same_board_and_refills() {
+        let play = |seed: u64| {
+            let mut rng = seeded_rng(seed);
+            let mut game_grid = GameGrid::with_size(8, 12);
+            generate_grid(&mut game_grid, &mut rng, MIN_STARTING_MOVES);
+            // black out the bottom row and let new candies drop in
+            for x in 0..8 {
+                game_grid.set_black(x, 11);
+            }
+            game_grid.collapse();
+            refill_grid(&mut game_grid, &mut rng);
+            game_grid
+        };
+        assert!(play(42) == play(42));
+        assert!(play(42) != play(43));
+    }
+
+    #[test]
+    fn chocolate_spreads_onto_plain_candies() {
+        let mut game_grid = GameGrid::with_size(3, 1);
+        game_grid.fill_space(0, 0, CandyColor::Chocolate);
+        game_grid.fill_space(1, 0, CandyColor::White);
+        game_grid.fill_space(2, 0, CandyColor::White);
+        let mut rng = seeded_rng(1);
+        assert!(spread_chocolate(&mut game_grid, &mut rng));
+        assert_eq!(game_grid.color(1, 0), CandyColor::Chocolate);
+
+        // a locked candy is safe, so there's nowhere left to go
+        game_grid.set_blocker(2, 0, Blocker::Lock);
+        assert!(!spread_chocolate(&mut game_grid, &mut rng));
+    }
+
+    #[test]
+    fn generate_grid_keeps_placed_spaces() {
+        let mut game_grid = GameGrid::with_size(7, 7);
+        game_grid.fill_space(3, 0, CandyColor::Ingredient);
+        game_grid.fill_space(0, 6, CandyColor::DarkBlue);
+        game_grid.fill_space(1, 6, CandyColor::DarkBlue);
+        assert!(generate_grid(
+            &mut game_grid,
+            &mut seeded_rng(7),
+            MIN_STARTING_MOVES
+        ));
+        assert_eq!(game_grid.color(3, 0), CandyColor::Ingredient);
+        assert_eq!(game_grid.color(0, 6), CandyColor::DarkBlue);
+        assert_eq!(game_grid.color(1, 6), CandyColor::DarkBlue);
+        assert!(game_grid.find_matches().is_empty());
+    }
+
+    #[test]
+    fn holes_are_never_filled() {
+        let mut game_grid = GameGrid::with_size(7, 7);
+        for (x, y) in [(0, 0), (6, 0), (3, 3), (0, 6), (6, 6)] {
+            game_grid.make_hole(x, y);
+        }
+        let mut rng = seeded_rng(2);
+        assert!(generate_grid(&mut game_grid, &mut rng, MIN_STARTING_MOVES));
+        assert_eq!(game_grid.color(3, 3), CandyColor::Empty);
+
+        // black out a column through the hole and let new candies drop in around it
+        for y in 0..7 {
+            game_grid.set_black(3, y);
+        }
+        game_grid.collapse();
+        refill_grid(&mut game_grid, &mut rng);
+        assert_eq!(game_grid.color(3, 3), CandyColor::Empty);
+        assert!((0..7)
+            .filter(|&y| y != 3)
+            .all(|y| game_grid.color(3, y).is_candy()));
+    }
+
+    #[test]
+    fn deadlocked_boards_are_shuffled_into_playable_ones() {
+        let mut game_grid = GameGrid::with_size(6, 6);
+        // every color is two spaces from its nearest match along both rows and columns
+        for y in 0..6 {
+            for x in 0..6 {
+                game_grid.fill_space(x, y, CANDY_COLORS[(x + 2 * y) % 4]);
+            }
         }
+        game_grid.fill_space(2, 0, CandyColor::Ingredient);
+        game_grid.set_blocker(3, 5, Blocker::Lock);
+        let locked = game_grid.color(3, 5);
+        assert!(game_grid.find_matches().is_empty());
+        assert!(game_grid.find_valid_moves().is_empty());
 
-        x += 8.0;
-        y = 152.0;
+        assert!(shuffle_grid(&mut game_grid, &mut seeded_rng(3)));
+        assert!(game_grid.find_matches().is_empty());
+        assert!(!game_grid.find_valid_moves().is_empty());
+        assert_eq!(game_grid.color(2, 0), CandyColor::Ingredient);
+        assert_eq!(game_grid.color(3, 5), locked);
+        assert_eq!(game_grid.blocker(3, 5), Blocker::Lock);
     }
-}
\ No newline at end of file
+}
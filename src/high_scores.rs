@@ -0,0 +1,259 @@
+// The best scores played on this machine, a table for each level, board size and mode, written
+// out as TOML:
+//
+//     version = 1
+//
+//     [[tables]]
+//     level = "Classic"
+//     width = 8           # the classic game can be played on any size of board
+//     height = 8
+//     mode = "timed:45"   # as GameMode::tag writes it
+//
+//     [[tables.scores]]
+//     score = 5230
+//     player = "sam"
+//     date = "2026-10-17"
+//     seed = "1234"       # a string, since seeds can be too big for a TOML integer
+//
+// Where the text is kept is up to the frontend.
+use crate::file_version::read_version;
+use crate::level::Level;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// how many scores each table keeps
+pub const TABLE_SIZE: usize = 10;
+
+// the version of the file written now. Files from any other version are refused rather than
+// guessed at
+pub const HIGH_SCORES_VERSION: u32 = 1;
+
+// why saved high scores couldn't be read
+#[derive(Debug)]
+pub enum HighScoresError {
+    Parse(toml::de::Error),
+    // the file was written by another version of the game
+    Version(u32),
+}
+
+impl fmt::Display for HighScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HighScoresError::Parse(error) => write!(f, "{}", error),
+            HighScoresError::Version(version) => write!(
+                f,
+                "the high scores are version {}, but only version {} can be read",
+                version, HIGH_SCORES_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HighScoresError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighScore {
+    pub score: usize,
+    pub player: String,
+    // the day the game was played, as YYYY-MM-DD
+    pub date: String,
+    // the seed the board came from, so the game can be played again
+    #[serde(with = "crate::u64_string")]
+    pub seed: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Table {
+    level: String,
+    width: usize,
+    height: usize,
+    mode: String,
+    scores: Vec<HighScore>,
+}
+
+impl Table {
+    fn is_for(&self, level: &Level) -> bool {
+        self.level == level.name
+            && (self.width, self.height) == (level.board.width(), level.board.height())
+            && self.mode == level.mode.tag()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HighScoresFile {
+    version: u32,
+    #[serde(default)]
+    tables: Vec<Table>,
+}
+
+#[derive(Debug, Default)]
+pub struct HighScores {
+    tables: Vec<Table>,
+}
+
+impl HighScores {
+    pub fn from_toml(text: &str) -> Result<HighScores, HighScoresError> {
+        let version = read_version(text).map_err(HighScoresError::Parse)?;
+        if version != HIGH_SCORES_VERSION {
+            return Err(HighScoresError::Version(version));
+        }
+        let file: HighScoresFile = toml::from_str(text).map_err(HighScoresError::Parse)?;
+        Ok(HighScores {
+            tables: file.tables,
+        })
+    }
+
+    pub fn to_toml(&self) -> String {
+        #[derive(Serialize)]
+        struct FileRef<'a> {
+            version: u32,
+            tables: &'a [Table],
+        }
+        toml::to_string(&FileRef {
+            version: HIGH_SCORES_VERSION,
+            tables: &self.tables,
+        })
+        .expect("high scores are plain strings and numbers")
+    }
+
+    // the table for a level played on its board size and in its mode, best first
+    pub fn top(&self, level: &Level) -> &[HighScore] {
+        self.tables
+            .iter()
+            .find(|table| table.is_for(level))
+            .map_or(&[], |table| &table.scores)
+    }
+
+    // add a score to the table for a level, its board size and mode. Returns where it landed from
+    // the top, or None if it wasn't good enough to make the table. A score tied with one already
+    // in the table goes under it
+    pub fn record(&mut self, level: &Level, entry: HighScore) -> Option<usize> {
+        let index = match self.tables.iter().position(|table| table.is_for(level)) {
+            Some(index) => index,
+            None => {
+                self.tables.push(Table {
+                    level: level.name.clone(),
+                    width: level.board.width(),
+                    height: level.board.height(),
+                    mode: level.mode.tag(),
+                    scores: Vec::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+        let scores = &mut self.tables[index].scores;
+        let rank = scores.partition_point(|high_score| high_score.score >= entry.score);
+        if rank >= TABLE_SIZE {
+            return None;
+        }
+        scores.insert(rank, entry);
+        scores.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+}
+
+// a day counted from 1970-01-01 as YYYY-MM-DD
+pub fn date_string(days_since_epoch: u64) -> String {
+    // shift the epoch to 0000-03-01, so leap days fall at the end of each year
+    let days = days_since_epoch + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::GameMode;
+    use std::time::Duration;
+
+    fn entry(score: usize, player: &str) -> HighScore {
+        HighScore {
+            score,
+            player: player.to_string(),
+            date: "2026-10-17".to_string(),
+            seed: u64::MAX,
+        }
+    }
+
+    #[test]
+    fn tables_keep_the_best_scores_per_level_size_and_mode() {
+        let timed = Level::classic(8, 8, GameMode::Timed(Duration::from_secs(45)));
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.record(&timed, entry(500, "a")), Some(0));
+        assert_eq!(high_scores.record(&timed, entry(900, "b")), Some(0));
+        // a tie goes under the score it ties with
+        assert_eq!(high_scores.record(&timed, entry(500, "c")), Some(2));
+        assert_eq!(
+            high_scores.record(&Level::classic(8, 8, GameMode::Moves(30)), entry(100, "d")),
+            Some(0)
+        );
+
+        let players: Vec<_> = high_scores
+            .top(&timed)
+            .iter()
+            .map(|high_score| high_score.player.as_str())
+            .collect();
+        assert_eq!(players, ["b", "a", "c"]);
+        let mut jelly_jam = timed.clone();
+        jelly_jam.name = "Jelly Jam".to_string();
+        assert!(high_scores.top(&jelly_jam).is_empty());
+        // the classic game on another size of board is another game
+        let bigger = Level::classic(9, 9, timed.mode);
+        assert!(high_scores.top(&bigger).is_empty());
+
+        for _ in 0..TABLE_SIZE {
+            high_scores.record(&timed, entry(1000, "e"));
+        }
+        assert_eq!(high_scores.top(&timed).len(), TABLE_SIZE);
+        assert_eq!(high_scores.record(&timed, entry(999, "f")), None);
+    }
+
+    #[test]
+    fn high_scores_are_read_back_from_their_file() {
+        let mut high_scores = HighScores::default();
+        let mut diamond = Level::classic(7, 7, GameMode::Endless);
+        diamond.name = "Diamond".to_string();
+        high_scores.record(&diamond, entry(1234, "sam"));
+        let text = high_scores.to_toml();
+
+        let read = HighScores::from_toml(&text).unwrap();
+        assert_eq!(read.top(&diamond), [entry(1234, "sam")]);
+
+        let newer = text.replace("version = 1", "version = 2");
+        assert!(matches!(
+            HighScores::from_toml(&newer),
+            Err(HighScoresError::Version(2))
+        ));
+        assert!(matches!(
+            HighScores::from_toml("tables = 3"),
+            Err(HighScoresError::Parse(_))
+        ));
+        let unknown = text.replace("version = 1", "version = 1\nbest = 1234");
+        assert!(matches!(
+            HighScores::from_toml(&unknown),
+            Err(HighScoresError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn dates_are_counted_from_the_epoch() {
+        assert_eq!(date_string(0), "1970-01-01");
+        assert_eq!(date_string(11_016), "2000-02-29");
+        assert_eq!(date_string(20_743), "2026-10-17");
+    }
+}
@@ -0,0 +1,55 @@
+use crate::font::{truncated, Align};
+use crate::screens::Frame;
+use scene2d::grid::{BOARD_ORIGIN, CELL_SIZE};
+use scene2d::{GameMode, GameState};
+use std::time::Duration;
+
+// the strip above the board the HUD is drawn in, in world units
+pub const HUD_HEIGHT: f32 = 2.0 * CELL_SIZE;
+
+// how big the HUD's text is, how far apart its lines are and how far it keeps from the sides of
+// the window
+const TEXT_SIZE: f32 = CELL_SIZE / 2.0;
+const LINE_HEIGHT: f32 = TEXT_SIZE + 1.0;
+const MARGIN: f32 = 1.0;
+
+// the level name across the top, then the score on the left and the time or moves left on the
+// right, a line apart so they can't run into each other on a narrow board. Lines too long for the
+// board are cut short
+pub fn render(game: &GameState, frame: &mut Frame) {
+    let [width, height] = game.grid().screen_size();
+    let room = width - 2.0 * MARGIN;
+    let top = BOARD_ORIGIN[1] + height + HUD_HEIGHT;
+    let line = |x: f32, index: usize| [x, top - (index + 1) as f32 * LINE_HEIGHT];
+    let left = BOARD_ORIGIN[0] + MARGIN;
+    let right = BOARD_ORIGIN[0] + width - MARGIN;
+
+    let name = truncated(&game.level().name.to_uppercase(), room, TEXT_SIZE);
+    frame.text(
+        &name,
+        line(BOARD_ORIGIN[0] + width / 2.0, 0),
+        TEXT_SIZE,
+        Align::Center,
+    );
+
+    if game.mode().keeps_score() {
+        let score = truncated(&format!("SCORE {}", game.score()), room, TEXT_SIZE);
+        frame.text(&score, line(left, 1), TEXT_SIZE, Align::Left);
+    }
+
+    let limit = match game.mode() {
+        GameMode::Timed(_) => format!("TIME {}", clock(game.time_left().unwrap_or_default())),
+        GameMode::Moves(_) => format!("MOVES {}", game.moves_left().unwrap_or_default()),
+        GameMode::Endless => "ENDLESS".to_string(),
+        GameMode::Zen => "ZEN".to_string(),
+    };
+    let limit = truncated(&limit, room, TEXT_SIZE);
+    frame.text(&limit, line(right, 2), TEXT_SIZE, Align::Right);
+}
+
+// the time left as minutes and seconds. Part of a second counts as a whole one, so the clock only
+// shows 0:00 once it has run out
+fn clock(time_left: Duration) -> String {
+    let seconds = time_left.as_millis().div_ceil(1000);
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
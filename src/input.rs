@@ -36,6 +36,9 @@ impl Input {
     pub fn is_key_released(&self, kc: Key) -> bool {
         !self.now_keys[kc as usize] && self.prev_keys[kc as usize]
     }
+    pub fn is_ctrl_down(&self) -> bool {
+        self.is_key_down(Key::LControl) || self.is_key_down(Key::RControl)
+    }
     pub fn is_mouse_down(&self, button: MouseButton) -> bool {
         self.now_mouse[Self::mouse_button_to_usize(button)]
     }
@@ -0,0 +1,268 @@
+use crate::grid::{CandyColor, GameGrid, CANDY_COLORS};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+// how long the classic game lasts
+pub const GAME_DURATION: Duration = Duration::from_secs(45);
+
+// how many moves a move-limited game gives unless told otherwise
+pub const MOVE_LIMIT: usize = 30;
+
+// what limits a game and how it ends
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    // the game ends when the clock runs out. Winning early pays out for the time left
+    Timed(Duration),
+    // the game ends when the moves run out. Winning early pays out for the moves left
+    Moves(usize),
+    // no limits: the game goes on until the board runs out of moves instead of being shuffled
+    Endless,
+    // no limits and nothing ends the game. The board is shuffled when it runs out of moves and
+    // no score is kept
+    Zen,
+}
+
+impl GameMode {
+    // how long the player has, if the game is timed
+    pub fn time_limit(self) -> Option<Duration> {
+        match self {
+            GameMode::Timed(limit) => Some(limit),
+            _ => None,
+        }
+    }
+
+    // how many moves the player has, if they are limited
+    pub fn move_limit(self) -> Option<usize> {
+        match self {
+            GameMode::Moves(limit) => Some(limit),
+            _ => None,
+        }
+    }
+
+    pub fn keeps_score(self) -> bool {
+        self != GameMode::Zen
+    }
+
+    // whether the game can end at all
+    pub fn can_end(self) -> bool {
+        self != GameMode::Zen
+    }
+
+    // a mode as it is written on the command line and in saved files: timed:SECONDS,
+    // moves:COUNT, endless or zen
+    pub fn tag(self) -> String {
+        match self {
+            GameMode::Timed(limit) => format!("timed:{}", limit.as_secs()),
+            GameMode::Moves(limit) => format!("moves:{}", limit),
+            GameMode::Endless => "endless".to_string(),
+            GameMode::Zen => "zen".to_string(),
+        }
+    }
+
+    // read a mode back from its tag. The limit is optional and defaults to the classic game's
+    pub fn parse(tag: &str) -> Option<GameMode> {
+        let (name, limit) = match tag.split_once(':') {
+            Some((name, limit)) => (
+                name,
+                Some(limit.parse::<u64>().ok().filter(|&limit| limit > 0)?),
+            ),
+            None => (tag, None),
+        };
+        match (name, limit) {
+            ("timed", limit) => Some(GameMode::Timed(
+                limit.map_or(GAME_DURATION, Duration::from_secs),
+            )),
+            ("moves", limit) => Some(GameMode::Moves(
+                limit.map_or(MOVE_LIMIT, |limit| limit as usize),
+            )),
+            ("endless", None) => Some(GameMode::Endless),
+            ("zen", None) => Some(GameMode::Zen),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameMode::Timed(limit) => write!(f, "timed, {} seconds", limit.as_secs()),
+            GameMode::Moves(limit) => write!(f, "{} moves", limit),
+            GameMode::Endless => write!(f, "endless"),
+            GameMode::Zen => write!(f, "zen"),
+        }
+    }
+}
+
+// something the player has to do to win a level
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    // reach at least this score
+    Score(usize),
+    // black out this many candies of one color
+    Collect { color: CandyColor, count: usize },
+    // clear every layer of jelly off the board
+    ClearJelly,
+    // bring every ingredient down to the bottom of the board
+    Ingredients,
+}
+
+impl Objective {
+    // whether the objective has been met, judged from the board after a move and what the
+    // player has done so far
+    pub fn is_met(&self, game_grid: &GameGrid, score: usize, progress: &Progress) -> bool {
+        match *self {
+            Objective::Score(target) => score >= target,
+            Objective::Collect { color, count } => progress.collected(color) >= count,
+            Objective::ClearJelly => game_grid.jelly_left() == 0,
+            Objective::Ingredients => game_grid.ingredients_left() == 0,
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::Score(target) => write!(f, "score {} points", target),
+            Objective::Collect { color, count } => {
+                write!(f, "collect {} {:?} candies", count, color)
+            }
+            Objective::ClearJelly => write!(f, "clear all the jelly"),
+            Objective::Ingredients => write!(f, "bring down all the ingredients"),
+        }
+    }
+}
+
+// what the player has done towards a level's objectives that can't be read off the board
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    // candies blacked out of each color, in CANDY_COLORS order
+    collected: [usize; CANDY_COLORS.len()],
+    // ingredients brought down to the bottom
+    pub ingredients: usize,
+    // moves made, not counting rejected swaps
+    pub moves: usize,
+}
+
+impl Progress {
+    pub fn collected(&self, color: CandyColor) -> usize {
+        CANDY_COLORS
+            .iter()
+            .position(|&candy| candy == color)
+            .map_or(0, |index| self.collected[index])
+    }
+
+    pub fn collect(&mut self, colors: &[CandyColor]) {
+        for &color in colors {
+            if let Some(index) = CANDY_COLORS.iter().position(|&candy| candy == color) {
+                self.collected[index] += 1;
+            }
+        }
+    }
+}
+
+// how a game ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    // every objective was met. Stars go from 0 to 3 by score
+    Won { stars: usize },
+    // time, moves or the board ran out first
+    Lost,
+}
+
+// the board a game starts on and what the player has to do on it
+#[derive(Clone)]
+pub struct Level {
+    pub name: String,
+    // the board before the game starts, with any jelly, ingredients and candies the level places.
+    // Empty spaces are filled with random candies when the game starts
+    pub board: GameGrid,
+    // all of these have to be met to win. A level with none is won by lasting until the end
+    pub objectives: Vec<Objective>,
+    pub mode: GameMode,
+    // the scores needed for one, two and three stars
+    pub stars: [usize; 3],
+}
+
+impl Level {
+    // the classic game: score as much as possible before the clock runs out
+    pub fn timed(width: usize, height: usize) -> Self {
+        Level::classic(width, height, GameMode::Timed(GAME_DURATION))
+    }
+
+    // a random board without objectives, played in the given mode
+    pub fn classic(width: usize, height: usize, mode: GameMode) -> Self {
+        Level {
+            name: "Classic".to_string(),
+            board: GameGrid::with_size(width, height),
+            objectives: Vec::new(),
+            mode,
+            stars: [1000, 2500, 5000],
+        }
+    }
+
+    pub fn objectives_met(&self, game_grid: &GameGrid, score: usize, progress: &Progress) -> bool {
+        self.objectives
+            .iter()
+            .all(|objective| objective.is_met(game_grid, score, progress))
+    }
+
+    // how many stars a score is worth
+    pub fn stars_for(&self, score: usize) -> usize {
+        self.stars
+            .iter()
+            .filter(|&&threshold| score >= threshold)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objectives_are_judged_from_board_and_progress() {
+        let mut board = GameGrid::with_size(3, 3);
+        board.set_jelly(1, 1, 2);
+        board.fill_space(0, 0, CandyColor::Ingredient);
+        let mut progress = Progress::default();
+
+        let collect = Objective::Collect {
+            color: CandyColor::DarkBlue,
+            count: 3,
+        };
+        assert!(!collect.is_met(&board, 0, &progress));
+        progress.collect(&[CandyColor::DarkBlue; 3]);
+        assert!(collect.is_met(&board, 0, &progress));
+
+        assert!(!Objective::ClearJelly.is_met(&board, 0, &progress));
+        board.set_jelly(1, 1, 0);
+        assert!(Objective::ClearJelly.is_met(&board, 0, &progress));
+
+        assert!(!Objective::Ingredients.is_met(&board, 0, &progress));
+        assert!(Objective::Score(100).is_met(&board, 100, &progress));
+    }
+
+    #[test]
+    fn modes_are_read_back_from_their_tags() {
+        for mode in [
+            GameMode::Timed(Duration::from_secs(60)),
+            GameMode::Moves(20),
+            GameMode::Endless,
+            GameMode::Zen,
+        ] {
+            assert_eq!(GameMode::parse(&mode.tag()), Some(mode));
+        }
+        assert_eq!(GameMode::parse("moves"), Some(GameMode::Moves(MOVE_LIMIT)));
+        assert_eq!(GameMode::parse("timed:0"), None);
+        assert_eq!(GameMode::parse("zen:5"), None);
+    }
+
+    #[test]
+    fn stars_follow_the_thresholds() {
+        let level = Level::timed(8, 8);
+        assert_eq!(level.stars_for(0), 0);
+        assert_eq!(level.stars_for(level.stars[0]), 1);
+        assert_eq!(level.stars_for(level.stars[2] + 1), 3);
+    }
+}
@@ -0,0 +1,615 @@
+// Levels written out as TOML, like the ones in the levels folder:
+//
+//     name = "Jelly Jam"
+//     width = 7
+//     height = 7
+//     colors = ["White", "DarkBlue", "LightBlue", "DarkOrange"]  # optional, every color by default
+//     time_limit = 60                                           # seconds, optional
+//     move_limit = 20                                           # optional, not with time_limit
+//     stars = [1000, 2500, 5000]                                # optional
+//
+//     # one row per line from the top, one character per space: . for a random candy, I for an
+//     # ingredient, 1 to 6 for a candy of that color in CANDY_COLORS order, or X for a hole that
+//     # isn't part of the board
+//     layout = """
+//     X..I..X
+//     ......."""
+//
+//     # layers of jelly on each space, . for none
+//     jelly = """
+//     .......
+//     ..222.."""
+//
+//     # S for stone, C for chocolate, I for ice or L for a lock over the candy, . for none
+//     blockers = """
+//     S.....S
+//     ..LIL.."""
+//
+//     [[objectives]]
+//     type = "score"    # or "collect" with a color and count, "jelly" or "ingredients"
+//     target = 5000
+//
+// The grids are optional and default to random candies without jelly or blockers. A level with
+// neither a time_limit nor a move_limit is endless.
+use crate::grid::{Blocker, CandyColor, GameGrid, CANDY_COLORS, MAX_BOARD_SIDE, MIN_BOARD_SIDE};
+use crate::level::{GameMode, Level, Objective};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use toml::Spanned;
+
+// why a level file couldn't be loaded
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    // the file isn't valid TOML or a value has the wrong type. The message says where
+    Parse(toml::de::Error),
+    // the file parsed but doesn't describe a playable level. `line` is the line of the file the
+    // problem is on, when it is down to one line
+    Invalid {
+        line: Option<usize>,
+        message: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "couldn't read the level: {}", error),
+            LevelError::Parse(error) => write!(f, "{}", error),
+            LevelError::Invalid {
+                line: Some(line),
+                message,
+            } => write!(f, "line {}: {}", line, message),
+            LevelError::Invalid {
+                line: None,
+                message,
+            } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+fn invalid(line: Option<usize>, message: impl Into<String>) -> LevelError {
+    LevelError::Invalid {
+        line,
+        message: message.into(),
+    }
+}
+
+fn on_hole(line: usize, what: &str, x: usize) -> LevelError {
+    invalid(
+        Some(line),
+        format!("{} in column {} is on a hole in the layout", what, x + 1),
+    )
+}
+
+// the candy colors as they are spelled in level files
+#[derive(Copy, Clone, Deserialize)]
+enum ColorName {
+    White,
+    DarkBlue,
+    LightBlue,
+    LightOrange,
+    DarkOrange,
+    WhiteOrange,
+}
+
+impl From<ColorName> for CandyColor {
+    fn from(name: ColorName) -> Self {
+        match name {
+            ColorName::White => CandyColor::White,
+            ColorName::DarkBlue => CandyColor::DarkBlue,
+            ColorName::LightBlue => CandyColor::LightBlue,
+            ColorName::LightOrange => CandyColor::LightOrange,
+            ColorName::DarkOrange => CandyColor::DarkOrange,
+            ColorName::WhiteOrange => CandyColor::WhiteOrange,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectiveEntry {
+    Score { target: usize },
+    Collect { color: ColorName, count: usize },
+    Jelly,
+    Ingredients,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    name: String,
+    width: Spanned<usize>,
+    height: Spanned<usize>,
+    colors: Option<Spanned<Vec<ColorName>>>,
+    time_limit: Option<Spanned<u64>>,
+    move_limit: Option<Spanned<usize>>,
+    stars: Option<Spanned<[usize; 3]>>,
+    layout: Option<Spanned<String>>,
+    jelly: Option<Spanned<String>>,
+    blockers: Option<Spanned<String>>,
+    #[serde(default)]
+    objectives: Vec<ObjectiveEntry>,
+}
+
+// the line of the file a byte offset is on, counting from 1
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+// the line a whole value starts on
+fn line_of_start<T>(text: &str, value: &Spanned<T>) -> Option<usize> {
+    Some(line_at(text, value.span().start))
+}
+
+// the line of the file the first row of a grid is on
+fn first_row_line(text: &str, grid: &Spanned<String>) -> usize {
+    let start = grid.span().start;
+    // a newline straight after the opening quotes isn't part of the string, so the first row is
+    // on the line after them
+    let raw = &text[start..];
+    let skipped = (raw.starts_with("\"\"\"") || raw.starts_with("'''"))
+        && raw[3..].trim_start_matches('\r').starts_with('\n');
+    line_at(text, start) + skipped as usize
+}
+
+// split a grid written as a multi-line string into its rows, checking it is the size of the board.
+// Returns each row's characters along with the line of the file it is on
+fn grid_rows(
+    text: &str,
+    field: &str,
+    grid: &Spanned<String>,
+    width: usize,
+    height: usize,
+) -> Result<Vec<(usize, Vec<char>)>, LevelError> {
+    let first_line = first_row_line(text, grid);
+
+    let mut rows: Vec<(usize, Vec<char>)> = grid
+        .get_ref()
+        .lines()
+        .enumerate()
+        .map(|(index, row)| {
+            (
+                first_line + index,
+                row.trim_end_matches('\r').chars().collect(),
+            )
+        })
+        .collect();
+    // let the closing quotes go on a line of their own
+    if rows.last().is_some_and(|(_, row)| row.is_empty()) {
+        rows.pop();
+    }
+
+    if rows.len() != height {
+        return Err(invalid(
+            line_of_start(text, grid),
+            format!(
+                "{} has {} rows, but the board is {} high",
+                field,
+                rows.len(),
+                height
+            ),
+        ));
+    }
+    for (line, row) in &rows {
+        if row.len() != width {
+            return Err(invalid(
+                Some(*line),
+                format!(
+                    "{} row has {} spaces, but the board is {} wide",
+                    field,
+                    row.len(),
+                    width
+                ),
+            ));
+        }
+    }
+    Ok(rows)
+}
+
+impl Level {
+    // read a level from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LevelError> {
+        let text = std::fs::read_to_string(path).map_err(LevelError::Io)?;
+        Level::from_toml(&text)
+    }
+
+    // build a level from the text of a level file
+    pub fn from_toml(text: &str) -> Result<Level, LevelError> {
+        let file: LevelFile = toml::from_str(text).map_err(LevelError::Parse)?;
+
+        for (side, size) in [("width", &file.width), ("height", &file.height)] {
+            if !(MIN_BOARD_SIDE..=MAX_BOARD_SIDE).contains(size.get_ref()) {
+                return Err(invalid(
+                    line_of_start(text, size),
+                    format!(
+                        "the board's {} is {}, but has to be from {} to {}",
+                        side,
+                        size.get_ref(),
+                        MIN_BOARD_SIDE,
+                        MAX_BOARD_SIDE
+                    ),
+                ));
+            }
+        }
+        let (width, height) = (*file.width.get_ref(), *file.height.get_ref());
+        let mut board = GameGrid::with_size(width, height);
+
+        if let Some(colors) = &file.colors {
+            let names = colors.get_ref();
+            let list: Vec<CandyColor> = names.iter().map(|&name| name.into()).collect();
+            if list.len() < 3 {
+                return Err(invalid(
+                    line_of_start(text, colors),
+                    "levels need at least three colors",
+                ));
+            }
+            if let Some(repeat) = list
+                .iter()
+                .enumerate()
+                .find(|&(index, color)| list[..index].contains(color))
+            {
+                return Err(invalid(
+                    line_of_start(text, colors),
+                    format!("{:?} is listed twice in colors", repeat.1),
+                ));
+            }
+            board.set_colors(list);
+        }
+
+        if let Some(layout) = &file.layout {
+            let rows = grid_rows(text, "layout", layout, width, height)?;
+            for (y, (line, row)) in rows.into_iter().enumerate() {
+                for (x, &tile) in row.iter().enumerate() {
+                    match tile {
+                        '.' => {}
+                        'X' => board.make_hole(x, y),
+                        'I' => board.fill_space(x, y, CandyColor::Ingredient),
+                        '1'..='6' => {
+                            let color = CANDY_COLORS[tile as usize - '1' as usize];
+                            board.fill_space(x, y, color)
+                        }
+                        _ => {
+                            return Err(invalid(
+                                Some(line),
+                                format!(
+                                    "unknown layout tile '{}' in column {}. Expected ., X, I or a color from 1 to 6",
+                                    tile,
+                                    x + 1
+                                ),
+                            ))
+                        }
+                    }
+                }
+            }
+            // generated candies never make matches, so placed ones mustn't either
+            if let Some(found) = board.find_matches().first() {
+                let (x, y) = found.cells[0];
+                return Err(invalid(
+                    Some(first_row_line(text, layout) + y),
+                    format!(
+                        "the layout starts with a match of {:?} in column {}",
+                        found.color,
+                        x + 1
+                    ),
+                ));
+            }
+        }
+
+        if let Some(jelly) = &file.jelly {
+            let rows = grid_rows(text, "jelly", jelly, width, height)?;
+            for (y, (line, row)) in rows.into_iter().enumerate() {
+                for (x, &tile) in row.iter().enumerate() {
+                    if tile != '.' && !board.is_filled(x, y) {
+                        return Err(on_hole(line, "jelly", x));
+                    }
+                    match tile {
+                        '.' => {}
+                        '1'..='9' => board.set_jelly(x, y, tile as u8 - b'0'),
+                        _ => {
+                            return Err(invalid(
+                                Some(line),
+                                format!(
+                                    "unknown jelly tile '{}' in column {}. Expected . or a number of layers from 1 to 9",
+                                    tile,
+                                    x + 1
+                                ),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(blockers) = &file.blockers {
+            let rows = grid_rows(text, "blockers", blockers, width, height)?;
+            for (y, (line, row)) in rows.into_iter().enumerate() {
+                for (x, &tile) in row.iter().enumerate() {
+                    if tile != '.' && !board.is_filled(x, y) {
+                        return Err(on_hole(line, "blocker", x));
+                    }
+                    let placed = board.color(x, y);
+                    // stone and chocolate take up the whole space, and covers only go on candies
+                    let clash = match tile {
+                        'S' | 'C' => placed != CandyColor::Empty,
+                        'I' | 'L' => placed == CandyColor::Ingredient,
+                        _ => false,
+                    };
+                    if clash {
+                        return Err(invalid(
+                            Some(line),
+                            format!(
+                                "blocker '{}' in column {} is on a space the layout already fills with {:?}",
+                                tile,
+                                x + 1,
+                                placed
+                            ),
+                        ));
+                    }
+                    match tile {
+                        '.' => {}
+                        'S' => board.fill_space(x, y, CandyColor::Stone),
+                        'C' => board.fill_space(x, y, CandyColor::Chocolate),
+                        'I' => board.set_blocker(x, y, Blocker::Ice),
+                        'L' => board.set_blocker(x, y, Blocker::Lock),
+                        _ => {
+                            return Err(invalid(
+                                Some(line),
+                                format!(
+                                "unknown blocker tile '{}' in column {}. Expected ., S, C, I or L",
+                                tile,
+                                x + 1
+                            ),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        let objectives = file
+            .objectives
+            .iter()
+            .map(|entry| match *entry {
+                ObjectiveEntry::Score { target } => Objective::Score(target),
+                ObjectiveEntry::Collect { color, count } => Objective::Collect {
+                    color: color.into(),
+                    count,
+                },
+                ObjectiveEntry::Jelly => Objective::ClearJelly,
+                ObjectiveEntry::Ingredients => Objective::Ingredients,
+            })
+            .collect::<Vec<_>>();
+        // objectives that could never be met, or are met before the game starts
+        for objective in &objectives {
+            let problem = match *objective {
+                Objective::Collect { color, .. } if !board.colors().contains(&color) => Some(
+                    format!("the level asks for {:?} candies but never drops any", color),
+                ),
+                Objective::ClearJelly if board.jelly_left() == 0 => {
+                    Some("the level asks to clear jelly but has none".to_string())
+                }
+                Objective::Ingredients if board.ingredients_left() == 0 => {
+                    Some("the level asks for ingredients but has none".to_string())
+                }
+                _ => None,
+            };
+            if let Some(message) = problem {
+                return Err(invalid(None, message));
+            }
+        }
+
+        let time_limit = match &file.time_limit {
+            Some(limit) if *limit.get_ref() == 0 => {
+                return Err(invalid(
+                    line_of_start(text, limit),
+                    "time_limit has to be at least 1 second",
+                ))
+            }
+            limit => limit
+                .as_ref()
+                .map(|limit| Duration::from_secs(*limit.get_ref())),
+        };
+        let move_limit = match &file.move_limit {
+            Some(limit) if *limit.get_ref() == 0 => {
+                return Err(invalid(
+                    line_of_start(text, limit),
+                    "move_limit has to be at least 1",
+                ))
+            }
+            limit => limit.as_ref().map(|limit| *limit.get_ref()),
+        };
+        let mode = match (time_limit, move_limit) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    file.move_limit
+                        .as_ref()
+                        .and_then(|limit| line_of_start(text, limit)),
+                    "a level can have a time_limit or a move_limit, but not both",
+                ))
+            }
+            (Some(limit), None) => GameMode::Timed(limit),
+            (None, Some(limit)) => GameMode::Moves(limit),
+            (None, None) => GameMode::Endless,
+        };
+        let stars = match &file.stars {
+            Some(stars) => {
+                let thresholds = *stars.get_ref();
+                if thresholds[0] > thresholds[1] || thresholds[1] > thresholds[2] {
+                    return Err(invalid(
+                        line_of_start(text, stars),
+                        "stars have to go from the lowest score to the highest",
+                    ));
+                }
+                thresholds
+            }
+            None => Level::timed(width, height).stars,
+        };
+
+        Ok(Level {
+            name: file.name,
+            board,
+            objectives,
+            mode,
+            stars,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    // the levels shipped in the levels folder
+    const LEVELS: [&str; 5] = [
+        include_str!("../levels/jelly_jam.toml"),
+        include_str!("../levels/nut_drop.toml"),
+        include_str!("../levels/blue_harvest.toml"),
+        include_str!("../levels/rocky_road.toml"),
+        include_str!("../levels/diamond.toml"),
+    ];
+
+    fn error_line(text: &str) -> Option<usize> {
+        match Level::from_toml(text) {
+            Err(LevelError::Invalid { line, .. }) => line,
+            Err(other) => panic!("expected an invalid level, got {}", other),
+            Ok(_) => panic!("expected an invalid level"),
+        }
+    }
+
+    #[test]
+    fn shipped_levels_load() {
+        for text in LEVELS {
+            let level = Level::from_toml(text).unwrap();
+            assert!(
+                !level.objectives.is_empty(),
+                "{} has no objectives",
+                level.name
+            );
+            // the rest of the board has to fill in around what the level places
+            let game = GameState::with_level(level, 1);
+            assert!(game.grid().find_matches().is_empty());
+            assert!(!game.grid().find_valid_moves().is_empty());
+        }
+    }
+
+    #[test]
+    fn layout_places_candies_ingredients_and_jelly() {
+        let level = Level::from_toml(
+            r#"
+name = "Test"
+width = 3
+height = 3
+layout = """
+1.I
+...
+.2."""
+jelly = """
+...
+.2.
+..1"""
+"#,
+        )
+        .unwrap();
+        assert_eq!(level.board.color(0, 0), CandyColor::White);
+        assert_eq!(level.board.color(2, 0), CandyColor::Ingredient);
+        assert_eq!(level.board.color(1, 2), CandyColor::DarkBlue);
+        assert_eq!(level.board.color(1, 1), CandyColor::Empty);
+        assert_eq!(level.board.jelly(1, 1), 2);
+        assert_eq!(level.board.jelly(2, 2), 1);
+        assert_eq!(level.board.jelly_left(), 3);
+    }
+
+    #[test]
+    fn layout_cuts_holes() {
+        let base = "name = \"Test\"\nwidth = 3\nheight = 3\nlayout = \"\"\"\nX..\n...\n..X\"\"\"\n";
+        let level = Level::from_toml(base).unwrap();
+        assert!(!level.board.is_filled(0, 0));
+        assert!(!level.board.is_filled(2, 2));
+        assert!(level.board.is_filled(1, 1));
+
+        // jelly can't go in a hole
+        let jelly = format!("{}jelly = \"\"\"\n...\n...\n..1\"\"\"\n", base);
+        assert_eq!(error_line(&jelly), Some(11));
+    }
+
+    #[test]
+    fn blockers_go_on_the_board() {
+        let level = Level::from_toml(
+            r#"
+name = "Test"
+width = 3
+height = 3
+blockers = """
+S.C
+I..
+..L"""
+"#,
+        )
+        .unwrap();
+        assert_eq!(level.board.color(0, 0), CandyColor::Stone);
+        assert_eq!(level.board.color(2, 0), CandyColor::Chocolate);
+        assert_eq!(level.board.blocker(0, 1), Blocker::Ice);
+        assert_eq!(level.board.blocker(2, 2), Blocker::Lock);
+
+        // stone can't go where the layout already put an ingredient
+        let clash = "name = \"Test\"\nwidth = 3\nheight = 3\nlayout = \"\"\"\n..I\n...\n...\"\"\"\nblockers = \"\"\"\n..S\n...\n...\"\"\"\n";
+        assert_eq!(error_line(clash), Some(9));
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let base = "name = \"Test\"\nwidth = 3\nheight = 3\n";
+        // a bad tile on the second row of the layout, which starts on line 5
+        assert_eq!(
+            error_line(&format!("{}layout = \"\"\"\n...\n.x.\n...\"\"\"\n", base)),
+            Some(6)
+        );
+        // a row that is too short
+        assert_eq!(
+            error_line(&format!("{}layout = \"\"\"\n...\n...\n..\"\"\"\n", base)),
+            Some(7)
+        );
+        // a placed match on the first row
+        assert_eq!(
+            error_line(&format!("{}layout = \"\"\"\n111\n...\n...\"\"\"\n", base)),
+            Some(5)
+        );
+        assert_eq!(error_line(&format!("{}move_limit = 0\n", base)), Some(4));
+        // boards too small for a match or too big to see
+        assert_eq!(
+            error_line("name = \"Test\"\nwidth = 2\nheight = 3\n"),
+            Some(2)
+        );
+        assert_eq!(
+            error_line("name = \"Test\"\nwidth = 3\nheight = 51\n"),
+            Some(3)
+        );
+        assert_eq!(
+            error_line(&format!("{}time_limit = 60\nmove_limit = 20\n", base)),
+            Some(5)
+        );
+        assert_eq!(error_line(&format!("{}stars = [3, 2, 1]\n", base)), Some(4));
+        assert_eq!(
+            error_line(&format!("{}[[objectives]]\ntype = \"jelly\"\n", base)),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_files_are_parse_errors() {
+        for text in [
+            "name = \"Test\"\nwidth = 3\n",
+            "name = \"Test\"\nwidth = 3\nheight = 3\ncolors = [\"Pink\"]\n",
+            "name = \"Test\"\nwidth = 3\nheight = 3\nsize = 9\n",
+            "name = \"Test\"\nwidth = 3\nheight = 3\n[[objectives]]\ntype = \"win\"\n",
+        ] {
+            assert!(matches!(Level::from_toml(text), Err(LevelError::Parse(_))));
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// The rules of the game, kept apart from the wgpu frontend in main.rs so they can be tested,
+// played by bots or shown some other way
+mod file_version;
+pub mod game;
+pub mod grid;
+pub mod grid_generator;
+pub mod high_scores;
+pub mod level;
+pub mod level_file;
+pub mod replay;
+pub mod save;
+pub mod scoring;
+mod u64_string;
+
+pub use game::{Action, GameState, StepResult};
+pub use level::{GameMode, Level, Objective, Outcome};
@@ -1,5 +1,4 @@
 use bytemuck::{Pod, Zeroable};
-use num::abs;
 use std::{
     borrow::Cow,
     mem,
@@ -11,11 +10,19 @@ use winit::{
     window::Window,
 };
 
+mod font;
+mod hud;
 mod input;
-mod grid;
-use grid::GameGrid;
-mod grid_generator; // The name should match your module's file name
-use grid_generator::generate_grid;
+mod screens;
+mod storage;
+use hud::HUD_HEIGHT;
+use scene2d::grid::{
+    BOARD_ORIGIN, CELL_SIZE, DEFAULT_HEIGHT, DEFAULT_WIDTH, MAX_BOARD_SIDE, MIN_BOARD_SIDE,
+};
+use scene2d::high_scores::HighScores;
+use scene2d::replay::{Playback, Replay};
+use scene2d::{GameMode, GameState, Level};
+use screens::{Context, Frame, Playing, Replaying, Screen, ScreenStack, Settings, Title};
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
@@ -45,14 +52,235 @@ const SPRITES: SpriteOption = SpriteOption::Storage;
 const SPRITES: SpriteOption = SpriteOption::Uniform;
 #[cfg(feature = "vbuf")]
 const SPRITES: SpriteOption = SpriteOption::VertexBuffer;
-#[cfg(all(feature = "vbuf", feature = "uniform"))]
+#[cfg(all(feature = "vbuf", feature = "uniforms"))]
 compile_error!("Can't choose both vbuf and uniform sprite features");
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
+// how many sprites the sprite buffer has room for to start with. It grows when a frame needs more,
+// except in a uniform buffer, which always holds this many
+const MIN_SPRITE_CAPACITY: usize = 512;
+const SPRITE_UNIFORM_SIZE: u64 = (MIN_SPRITE_CAPACITY * mem::size_of::<GPUSprite>()) as u64;
+
+// the most one frame can move the game clock on. A frame only takes longer when the event loop
+// was held up, by a window drag or a breakpoint, and that shouldn't cost the player time
+const MAX_FRAME_TIME: Duration = Duration::from_millis(100);
+
+// convert a mouse position from window pixels to world units
+fn mouse_to_world(
+    camera: &GPUCamera,
+    config: &wgpu::SurfaceConfiguration,
+    mouse_pos: input::MousePos<f64>,
+) -> [f32; 2] {
+    [
+        camera.screen_pos[0] + (mouse_pos.x as f32 / config.width as f32) * camera.screen_size[0],
+        camera.screen_pos[1]
+            + (1.0 - mouse_pos.y as f32 / config.height as f32) * camera.screen_size[1],
+    ]
+}
+
+// a camera showing `view` world units from BOARD_ORIGIN, as big as it fits in the window and
+// centered, with the rest of the window left black
+fn fit_camera(view: [f32; 2], config: &wgpu::SurfaceConfiguration) -> GPUCamera {
+    let window_aspect = config.width.max(1) as f32 / config.height.max(1) as f32;
+    let screen_size = if view[0] / view[1] > window_aspect {
+        [view[0], view[0] / window_aspect]
+    } else {
+        [view[1] * window_aspect, view[1]]
+    };
+    GPUCamera {
+        screen_pos: [
+            BOARD_ORIGIN[0] - (screen_size[0] - view[0]) / 2.0,
+            BOARD_ORIGIN[1] - (screen_size[1] - view[1]) / 2.0,
+        ],
+        screen_size,
+    }
+}
+
+// the levels that ship with the game, in the order they are listed in the level select
+const SHIPPED_LEVELS: [&str; 5] = [
+    include_str!("../levels/jelly_jam.toml"),
+    include_str!("../levels/nut_drop.toml"),
+    include_str!("../levels/blue_harvest.toml"),
+    include_str!("../levels/rocky_road.toml"),
+    include_str!("../levels/diamond.toml"),
+];
+
+// settings that can be given on the command line
+struct Options {
+    board_width: usize,
+    board_height: usize,
+    // how long the player can go without clicking before a move is pointed out
+    hint_delay: Duration,
+    // the seed for the first board and its refills. Picked at random unless one is given
+    seed: Option<u64>,
+    // a level loaded from a file, played instead of the classic game
+    level: Option<Level>,
+    // the mode to play in, overriding the level's own if there is one
+    mode: Option<GameMode>,
+    // the name high scores are saved under
+    player: String,
+    // a recorded game to play back instead of playing
+    replay: Option<Replay>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Options {
+            board_width: DEFAULT_WIDTH,
+            board_height: DEFAULT_HEIGHT,
+            hint_delay: Duration::from_secs(5),
+            seed: None,
+            level: None,
+            mode: None,
+            // the login name is the best guess there is, and there is none in the browser
+            player: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "player".to_string()),
+            replay: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // board size given as WIDTHxHEIGHT, e.g. --size 9x9
+                "--size" => match args.next().as_deref().and_then(parse_size) {
+                    Some((width, height)) => {
+                        options.board_width = width;
+                        options.board_height = height;
+                    }
+                    None => println!("Invalid board size! Expected something like --size 9x9"),
+                },
+                // seconds of inactivity before a hint is shown, e.g. --hint-delay 2.5
+                "--hint-delay" => match args
+                    .next()
+                    .and_then(|delay| delay.parse::<f32>().ok())
+                    .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+                {
+                    Some(delay) => options.hint_delay = delay,
+                    None => println!("Invalid hint delay! Expected a number of seconds"),
+                },
+                // replay a board, e.g. --seed 1234
+                "--seed" => match args.next().and_then(|seed| seed.parse().ok()) {
+                    Some(seed) => options.seed = Some(seed),
+                    None => println!("Invalid seed! Expected a whole number"),
+                },
+                // play a level file, e.g. --level levels/jelly_jam.toml
+                "--level" => match args.next() {
+                    Some(path) => match Level::load(&path) {
+                        Ok(level) => options.level = Some(level),
+                        Err(error) => {
+                            println!("Invalid level {}: {}", path, error);
+                            std::process::exit(1);
+                        }
+                    },
+                    None => println!("Missing level! Expected a path to a level file"),
+                },
+                // e.g. --mode timed:60, --mode moves:20, --mode endless or --mode zen
+                "--mode" => match args.next().as_deref().and_then(GameMode::parse) {
+                    Some(mode) => options.mode = Some(mode),
+                    None => println!(
+                        "Invalid mode! Expected timed[:SECONDS], moves[:COUNT], endless or zen"
+                    ),
+                },
+                // the name to put on high scores, e.g. --name sam
+                "--name" => match args.next().filter(|name| !name.trim().is_empty()) {
+                    Some(name) => options.player = name.trim().to_string(),
+                    None => println!("Missing name! Expected a name to put on high scores"),
+                },
+                // play back a recorded game, e.g. --replay last_replay.toml
+                "--replay" => match args.next() {
+                    Some(path) => match Replay::load(&path) {
+                        Ok(replay) => options.replay = Some(replay),
+                        Err(error) => {
+                            println!("Invalid replay {}: {}", path, error);
+                            std::process::exit(1);
+                        }
+                    },
+                    None => println!("Missing replay! Expected a path to a replay file"),
+                },
+                _ => println!("Unknown argument: {}", arg),
+            }
+        }
+        options
+    }
+
+    // the classic game on a board of the size given, then every shipped level, then the level
+    // loaded from a file if there is one
+    fn levels(&self) -> Vec<Level> {
+        let mut levels = vec![Level::timed(self.board_width, self.board_height)];
+        levels.extend(SHIPPED_LEVELS.iter().map(|text| {
+            Level::from_toml(text).expect("shipped levels are checked by the level file tests")
+        }));
+        levels.extend(self.level.clone());
+        levels
+    }
+
+    // the level a replay was played on
+    fn replay_level(&self) -> Option<Level> {
+        let replay = self.replay.as_ref()?;
+        match replay.level(&self.levels()) {
+            Ok(level) => Some(level),
+            Err(error) => {
+                println!("Can't play the replay: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // the level the window is first sized for: the one replayed or loaded from a file, or the
+    // classic game
+    fn first_level(&self) -> Level {
+        self.replay_level()
+            .or_else(|| self.level.clone())
+            .unwrap_or_else(|| Level::timed(self.board_width, self.board_height))
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            mode: self.mode,
+            hint_delay: Some(self.hint_delay),
+            seed: self.seed,
+            player: self.player.clone(),
+        }
+    }
+}
+
+// the high scores saved by earlier runs. Ones that can't be read are left alone on disk until the
+// next score is saved over them
+fn load_high_scores() -> HighScores {
+    let Some(text) = storage::load(storage::HIGH_SCORES) else {
+        return HighScores::default();
+    };
+    HighScores::from_toml(&text).unwrap_or_else(|error| {
+        println!("Couldn't read the high scores, starting afresh: {}", error);
+        HighScores::default()
+    })
+}
+
+// a game left unfinished by an earlier run, to go on with from the title. One that can't be read,
+// say from another version of the game, is left on disk until the next game left is saved over it
+fn load_saved_game() -> Option<GameState> {
+    let text = storage::load(storage::SAVED_GAME)?;
+    match GameState::from_save(&text) {
+        Ok(game) => Some(game),
+        Err(error) => {
+            println!("Couldn't resume the saved game: {}", error);
+            None
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    let sides = MIN_BOARD_SIDE..=MAX_BOARD_SIDE;
+    if !sides.contains(&width) || !sides.contains(&height) {
+        return None;
+    }
+    Some((width, height))
+}
+
+async fn run(event_loop: EventLoop<()>, window: Window, options: Options) {
     let size = window.inner_size();
-    let start_time = Instant::now();
-    let game_duration = Duration::from_secs(45); // 45 seconds
-    let mut game_over = false;
+    let mut last_frame = Instant::now();
 
     log::info!("Use sprite mode {:?}", SPRITES);
 
@@ -288,91 +516,65 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         ],
     });
 
-    let camera = GPUCamera {
-        screen_pos: [80.0, 0.0],
-        screen_size: [80.0, 160.0],
-    };
-    let buffer_camera = device.create_buffer(&wgpu::BufferDescriptor {
+    // text is drawn the same way, from the glyph atlas instead of the sprite sheet
+    let (font_tex, _font_img) = load_texture(font::FONT_ATLAS, None, &device, &queue)
+        .await
+        .expect("Couldn't load font texture");
+    let view_font = font_tex.create_view(&wgpu::TextureViewDescriptor::default());
+    let font_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
-        size: bytemuck::bytes_of(&camera).len() as u64,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
+        layout: &texture_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view_font),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler_sprite),
+            },
+        ],
     });
 
-    let mut sprites: Vec<GPUSprite> = vec![
-        // these sprites initial locations are determined by sprite_position_x
-        // screen_region [x,y,z,w] = top left corner x, top left corner y, width, height
-        // sheet_region [x,y,z,w] = divided by spritesheet width, divided by spritesheet height, divided by spritesheet width, divided by spritesheet height, divided by spritesheet width, divided by spritesheet height,
-        GPUSprite {
-            screen_region: [0.0, 0.0, 8.0, 8.0],
-            sheet_region: [0.0 / 80.0, 0.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-        },
-    ];
-
-    let mut input = input::Input::default();
-    let mut game_grid = GameGrid::new();
-
-    let mut x: f32 = 80.0;
-    let mut y: f32 = 152.0;
-
-    generate_grid(x, y, &mut game_grid, &mut sprites);
-    
-    let green_sprite = GPUSprite {
-        screen_region: [300.0, 300.0, 8.0, 8.0],
-        sheet_region: [0.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0],
-    };
-    sprites.push(green_sprite);
-
-    let mut counter = 0;
-    let mut last_clicked = (x, y);
-    let mut last_cell_clicked = 0;
-    let mut color1 = [0.0, 0.0, 0.0, 0.0];
-    let mut color2 = [0.0, 0.0, 0.0, 0.0];
-    let mut score = 0;
-
-    const SPRITE_UNIFORM_SIZE: u64 = 512 * mem::size_of::<GPUSprite>() as u64;
+    // the menus are laid out over the first board to be played, until a game says otherwise
+    let [board_width, board_height] = options.first_level().board.screen_size();
+    let view = [board_width, board_height + HUD_HEIGHT];
+    let mut ctx = Context::new(view, options.settings(), options.levels());
+    ctx.high_scores = load_high_scores();
+    ctx.saved_game = load_saved_game();
+    // a replay or a level given on the command line is played straight away, with the title
+    // under it to go back to
+    let mut screens: Vec<Box<dyn Screen>> = vec![Box::new(Title::new())];
+    if let (Some(replay), Some(level)) = (&options.replay, options.replay_level()) {
+        screens.push(Box::new(Replaying::new(Playback::new(
+            replay.clone(),
+            level,
+        ))));
+    } else if let Some(level) = &options.level {
+        let level = ctx.settings.apply(level);
+        let seed = ctx.settings.take_seed();
+        screens.push(Box::new(Playing::new(level, seed)));
+    }
+    let mut stack = ScreenStack::new(screens, &mut ctx);
+    let mut frame = Frame::default();
+    let mut title = String::new();
 
-    let buffer_sprite = device.create_buffer(&wgpu::BufferDescriptor {
+    let camera = fit_camera(ctx.view, &config);
+    let buffer_camera = device.create_buffer(&wgpu::BufferDescriptor {
         label: None,
-        size: if SPRITES == SpriteOption::Uniform {
-            SPRITE_UNIFORM_SIZE
-        } else {
-            sprites.len() as u64 * std::mem::size_of::<GPUSprite>() as u64
-        },
-        usage: match SPRITES {
-            SpriteOption::Storage => wgpu::BufferUsages::STORAGE,
-            SpriteOption::Uniform => wgpu::BufferUsages::UNIFORM,
-            SpriteOption::VertexBuffer => wgpu::BufferUsages::VERTEX,
-        } | wgpu::BufferUsages::COPY_DST,
+        size: bytemuck::bytes_of(&camera).len() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     });
-    let sprite_bind_group = match SPRITES {
-        SpriteOption::VertexBuffer => device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &sprite_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer_camera.as_entire_binding(),
-            }],
-        }),
-        _ => device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &sprite_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer_camera.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: buffer_sprite.as_entire_binding(),
-                },
-            ],
-        }),
-    };
 
-    queue.write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
-    queue.write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
+    let mut sprites: Vec<GPUSprite> = Vec::new();
+    let mut sprite_capacity = MIN_SPRITE_CAPACITY;
+    let (mut buffer_sprite, mut sprite_bind_group) = create_sprite_buffer(
+        &device,
+        &sprite_bind_group_layout,
+        &buffer_camera,
+        sprite_capacity,
+    );
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -389,127 +591,46 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 window.request_redraw();
             }
             Event::RedrawRequested(_) => {
-                if !game_over {
-                    // handle timing
-                    let elapsed_time = start_time.elapsed();
-                    if elapsed_time >= game_duration {
-                        game_over = true;
-                        let bold_start = "\x1B[1m";
-                        let red_start = "\x1B[31m";
-                        let text_reset = "\x1B[0m"; // Reset text formatting
-
-                        // Print the entire line in bold
-                        print!("{}Game Over! Final Score: ", bold_start);
-
-                        // Print the score in red
-                        print!("{}{}{}", red_start, score, text_reset);
-
-                        // Print a newline to end the line
-                        println!();
-                        *control_flow = ControlFlow::Exit;
-                    }
-
-                    if input.is_key_pressed(winit::event::VirtualKeyCode::Down) {
-                        game_grid.print_space(0, 0);
-                    }
-
-                    if input.is_key_pressed(winit::event::VirtualKeyCode::Up) {
-                        game_grid.print_grid();
-                    }
-
-                    if input.is_mouse_released(winit::event::MouseButton::Left) {
-                        let mouse_pos = input.mouse_pos();
-                        // change the divisor to 32.0 for larger screens?
-                        let (mouse_x_norm, mouse_y_norm) =
-                            ((mouse_pos.x / 64.0), (mouse_pos.y / 64.0));
-
-                        // in these calculations, mouse_x_norm is the column, mouse_y_norm is the row
-                        let row = mouse_y_norm.floor() as usize;
-                        let column = mouse_x_norm.floor() as usize;
-
-                        // check for swap
-                        // if the counter is even, then save the clicked coords
-                        if counter % 2 == 0 {
-                            last_clicked = (column as f32, row as f32);
-                            last_cell_clicked = column * 20 + row + 1;
-                            sprites[201].screen_region[0] = column as f32;
-                            sprites[201].screen_region[1] = row as f32;
-                        }
-                        // if counter is odd, then swap current click with saved coords
-                        else {
-                            let (last_x, last_y) = last_clicked;
-                            if !game_grid.color_is_black(last_x as usize, last_y as usize)
-                                && !game_grid.color_is_black(column, row)
-                            {
-                                // swap the sprite locations
-                                let curr_cell = column * 20 + row + 1;
-                                let diff = curr_cell as f32 - last_cell_clicked as f32;
-
-                                // only swap if they are one apart
-                                if abs(diff) == 1.0 || abs(diff) == 20.0 {
-                                    // get colors
-                                    color1 = game_grid.get_color_coords(
-                                        mouse_y_norm.floor() as usize,
-                                        mouse_x_norm.floor() as usize,
-                                    );
-                                    color2 = game_grid
-                                        .get_color_coords(last_y as usize, last_x as usize);
-
-                                    // update the colors in the sprites vec
-                                    sprites[curr_cell as usize].sheet_region = color2;
-                                    sprites[last_cell_clicked as usize].sheet_region = color1;
-
-                                    // update the colors in the grid
-                                    game_grid.swap_colors(
-                                        mouse_x_norm.floor() as f32,
-                                        mouse_y_norm.floor() as f32,
-                                        last_clicked,
-                                    );
-                                } else {
-                                    println!("Invalid click! Can only click tiles one apart.");
-                                }
-                            } else {
-                                println!("Invalid click! Cannot swap a tile with nothing!");
-                            }
-
-                            let (blackout_horiz, start_x, mut start_y) =
-                                game_grid.check_blackout_horiz();
-                            if blackout_horiz != 202 {
-                                // set the four in a row to black
-                                for i in (blackout_horiz..(blackout_horiz + 80)).step_by(20) {
-                                    // set the sprite in the vec to black
-                                    sprites[i].sheet_region =
-                                        [0.0 / 80.0, 96.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
-                                    // set the color of the sprite in the grid to black
-                                    game_grid.set_black(start_x, start_y);
-                                    start_y += 1;
-                                }
-                                score += 4;
-                            }
-                            let (blackout_vert, mut start_x, start_y) =
-                                game_grid.check_blackout_vert();
-                            if blackout_vert != 202 {
-                                // set the four in a column to black
-                                for i in blackout_vert..(blackout_vert + 4) {
-                                    // set the sprite in the vec to black
-                                    sprites[i].sheet_region =
-                                        [0.0 / 80.0, 96.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
-                                    // set the color of the sprite in the grid to black
-                                    game_grid.set_black(start_x, start_y);
-                                    start_x += 1;
-                                }
-                                score += 4;
-                            }
-                            sprites[201].screen_region[0] = 300.0;
-                            sprites[201].screen_region[1] = 300.0;
-                        }
-                        counter += 1;
-                    }
+                // handle timing
+                let now = Instant::now();
+                let frame_time = (now - last_frame).min(MAX_FRAME_TIME);
+                last_frame = now;
+
+                // the top screen runs with the mouse where the camera showed it last frame
+                let camera = fit_camera(ctx.view, &config);
+                ctx.mouse = mouse_to_world(&camera, &config, ctx.input.mouse_pos());
+                if !stack.update(&mut ctx, frame_time) {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                if ctx.title != title {
+                    title = ctx.title.clone();
+                    window.set_title(&title);
                 }
+                ctx.input.next_frame();
+
+                // the screen may have changed what the camera has to show
+                let camera = fit_camera(ctx.view, &config);
+                frame.clear();
+                stack.render(&ctx, &mut frame);
+                sprites.clear();
+                sprites.extend_from_slice(frame.sheet_sprites());
+                sprites.extend_from_slice(frame.text_sprites());
+                // uniform buffers can't grow, so anything past what fits in one isn't drawn
+                if SPRITES == SpriteOption::Uniform {
+                    sprites.truncate(MIN_SPRITE_CAPACITY);
+                } else if sprites.len() > sprite_capacity {
+                    sprite_capacity = sprites.len().next_power_of_two();
+                    (buffer_sprite, sprite_bind_group) = create_sprite_buffer(
+                        &device,
+                        &sprite_bind_group_layout,
+                        &buffer_camera,
+                        sprite_capacity,
+                    );
+                }
+                let sheet_sprites = frame.sheet_sprites().len().min(sprites.len());
 
                 // Then send the data to the GPU!
-                input.next_frame();
-
                 queue.write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
                 queue.write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
 
@@ -545,58 +666,119 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     // this uses instanced drawing, but it would also be okay
                     // to draw 6 * sprites.len() vertices and use modular arithmetic
                     // to figure out which sprite we're drawing.
-                    rpass.draw(0..6, 0..201);
-                    // draw the green selector sprite
-                    rpass.draw(0..6, 201..202);
+                    rpass.draw(0..6, 0..sheet_sprites as u32);
+                    // then the text over all of it, from the glyph atlas
+                    rpass.set_bind_group(1, &font_bind_group, &[]);
+                    rpass.draw(0..6, sheet_sprites as u32..sprites.len() as u32);
                 }
 
                 queue.submit(Some(encoder.finish()));
                 frame.present();
                 window.request_redraw();
             }
+            // the screens are all taken off first, so a game being played is saved
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => {
+                stack.close(&mut ctx);
+                *control_flow = ControlFlow::Exit;
+            }
+            // the game pauses itself when the player switches to another window
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => {
+                let open = stack.focus_lost(&mut ctx);
+                if !open {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
             // WindowEvent->KeyboardInput: Keyboard input!
             Event::WindowEvent {
                 // Note this deeply nested pattern match
                 event: WindowEvent::KeyboardInput { input: key_ev, .. },
                 ..
             } => {
-                input.handle_key_event(key_ev);
+                ctx.input.handle_key_event(key_ev);
             }
             Event::WindowEvent {
                 event: WindowEvent::MouseInput { state, button, .. },
                 ..
             } => {
-                input.handle_mouse_button(state, button);
+                ctx.input.handle_mouse_button(state, button);
             }
             Event::WindowEvent {
                 event: WindowEvent::CursorMoved { position, .. },
                 ..
             } => {
-                input.handle_mouse_move(position);
+                ctx.input.handle_mouse_move(position);
             }
             _ => {}
         }
     });
 }
 
+// the sprite buffer, with room for `capacity` sprites, and the bind group that hands it and the
+// camera to the shader
+fn create_sprite_buffer(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer_camera: &wgpu::Buffer,
+    capacity: usize,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer_sprite = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: capacity as u64 * std::mem::size_of::<GPUSprite>() as u64,
+        usage: match SPRITES {
+            SpriteOption::Storage => wgpu::BufferUsages::STORAGE,
+            SpriteOption::Uniform => wgpu::BufferUsages::UNIFORM,
+            SpriteOption::VertexBuffer => wgpu::BufferUsages::VERTEX,
+        } | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let sprite_bind_group = match SPRITES {
+        SpriteOption::VertexBuffer => device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer_camera.as_entire_binding(),
+            }],
+        }),
+        _ => device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer_camera.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer_sprite.as_entire_binding(),
+                },
+            ],
+        }),
+    };
+    (buffer_sprite, sprite_bind_group)
+}
+
 fn main() {
     let event_loop = EventLoop::new();
+    let options = Options::from_args();
 
-    // Define the desired aspect ratio
-    let aspect_ratio = 80.0 / 160.0;
-
-    // Set a fixed width for the window
-    let window_width = 320.0; // For example, you can use 320 pixels width
-
-    // Calculate the corresponding height based on the aspect ratio
-    let window_height = window_width / aspect_ratio;
+    // Give every board space the same number of pixels, so the window matches the aspect ratio of
+    // the board and the HUD above it
+    let pixels_per_space = 32.0;
+    let board = options.first_level().board;
+    let window_width = pixels_per_space * board.width() as f64;
+    let window_height =
+        pixels_per_space * (board.height() as f64 + (HUD_HEIGHT / CELL_SIZE) as f64);
 
     // Create the window with the calculated dimensions
     let window = winit::window::WindowBuilder::new()
+        .with_title("candycrunch")
         .with_inner_size(winit::dpi::LogicalSize::new(window_width, window_height))
         .build(&event_loop)
         .unwrap();
@@ -604,7 +786,7 @@ fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
-        pollster::block_on(run(event_loop, window));
+        pollster::block_on(run(event_loop, window, options));
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -620,7 +802,7 @@ fn main() {
                     .ok()
             })
             .expect("couldn't append canvas to document body");
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
+        wasm_bindgen_futures::spawn_local(run(event_loop, window, options));
     }
 }
 
@@ -0,0 +1,439 @@
+// A game written down as its seed and every action that changed it, stamped with the game clock,
+// so it can be played back exactly as it went. Replays are written out as TOML:
+//
+//     version = 1
+//     level = "Classic"
+//     width = 8
+//     height = 8
+//     mode = "timed:45"       # as GameMode::tag writes it
+//     seed = "1234"           # a string, since seeds can be too big for a TOML integer
+//     length = 45000000000    # how long the game clock ran, in nanoseconds
+//     score = 5230            # the final score, checked on playback
+//
+//     [[actions]]
+//     type = "swap"           # or "undo" or "redo", which only have a time
+//     at = 2500000000         # the game clock when it was made, in nanoseconds
+//     first = [3, 4]
+//     second = [3, 5]
+//
+// The clock is kept to the nanosecond, so a move made just before time ran out can't round to
+// just after it.
+use crate::file_version::read_version;
+use crate::game::{Action, GameState};
+use crate::grid::{MAX_BOARD_SIDE, MIN_BOARD_SIDE};
+use crate::level::{GameMode, Level};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+// the version of the file written now. Files from any other version are refused, since the same
+// actions may not make the same game under other rules
+pub const REPLAY_VERSION: u32 = 1;
+
+// why a replay couldn't be loaded or played back
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    // the file was written by another version of the game
+    Version(u32),
+    // the file parsed but something in it makes no sense
+    Invalid(String),
+    // none of the levels there are is the one the replay was played on
+    UnknownLevel(String),
+    // the replay played out to a different score than it recorded
+    ScoreMismatch { recorded: usize, played: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "couldn't read the replay: {}", error),
+            ReplayError::Parse(error) => write!(f, "{}", error),
+            ReplayError::Version(version) => write!(
+                f,
+                "the replay is version {}, but only version {} can be played",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Invalid(message) => write!(f, "{}", message),
+            ReplayError::UnknownLevel(name) => write!(f, "there is no level called {}", name),
+            ReplayError::ScoreMismatch { recorded, played } => write!(
+                f,
+                "the replay recorded a score of {}, but played out to {}",
+                recorded, played
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+// an action with the time on the game clock it was made at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimedAction {
+    pub at: Duration,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    // the name of the level played, and the size of its board to tell boards of the classic game
+    // apart
+    pub level: String,
+    pub width: usize,
+    pub height: usize,
+    pub mode: GameMode,
+    pub seed: u64,
+    // swaps, undos and redos, oldest first. Nothing else changes the game but the clock
+    pub actions: Vec<TimedAction>,
+    // how long the game clock ran, and the score at the end
+    pub length: Duration,
+    pub score: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ActionEntry {
+    Swap {
+        at: u64,
+        first: [usize; 2],
+        second: [usize; 2],
+    },
+    Undo {
+        at: u64,
+    },
+    Redo {
+        at: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayFile {
+    version: u32,
+    level: String,
+    width: usize,
+    height: usize,
+    mode: String,
+    #[serde(with = "crate::u64_string")]
+    seed: u64,
+    length: u64,
+    score: usize,
+    #[serde(default)]
+    actions: Vec<ActionEntry>,
+}
+
+fn nanos(time: Duration) -> u64 {
+    time.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+impl Replay {
+    // start recording a game that hasn't been played yet
+    pub fn new(game: &GameState) -> Self {
+        let level = game.level();
+        Replay {
+            level: level.name.clone(),
+            width: level.board.width(),
+            height: level.board.height(),
+            mode: level.mode,
+            seed: game.seed(),
+            actions: Vec::new(),
+            length: Duration::ZERO,
+            score: 0,
+        }
+    }
+
+    // write down an action the game has just taken. Only actions that changed it need to be
+    pub fn record(&mut self, game: &GameState, action: Action) {
+        self.actions.push(TimedAction {
+            at: game.elapsed(),
+            action,
+        });
+    }
+
+    // write down how the game ended
+    pub fn finish(&mut self, game: &GameState) {
+        self.length = game.elapsed();
+        self.score = game.score();
+    }
+
+    // the level the replay was played on, out of the levels there are. The classic game can be
+    // played on any size of board, so it is made to fit
+    pub fn level(&self, levels: &[Level]) -> Result<Level, ReplayError> {
+        let classic = Level::classic(self.width, self.height, self.mode);
+        let mut level = levels
+            .iter()
+            .find(|level| {
+                level.name == self.level
+                    && level.board.width() == self.width
+                    && level.board.height() == self.height
+            })
+            .cloned()
+            .or_else(|| (classic.name == self.level).then_some(classic))
+            .ok_or_else(|| ReplayError::UnknownLevel(self.level.clone()))?;
+        level.mode = self.mode;
+        Ok(level)
+    }
+
+    // read a replay from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        let text = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+        Replay::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Replay, ReplayError> {
+        let version = read_version(text).map_err(ReplayError::Parse)?;
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::Version(version));
+        }
+        let file: ReplayFile = toml::from_str(text).map_err(ReplayError::Parse)?;
+        // the board is made to this size on playback, so it has to be one the game can play on
+        let sides = MIN_BOARD_SIDE..=MAX_BOARD_SIDE;
+        if !sides.contains(&file.width) || !sides.contains(&file.height) {
+            return Err(ReplayError::Invalid(format!(
+                "the board is {}x{}, but its sides have to be from {} to {}",
+                file.width, file.height, MIN_BOARD_SIDE, MAX_BOARD_SIDE
+            )));
+        }
+        let mode = GameMode::parse(&file.mode)
+            .ok_or_else(|| ReplayError::Invalid(format!("{} isn't a mode", file.mode)))?;
+        let actions = file
+            .actions
+            .into_iter()
+            .map(|entry| {
+                let (at, action) = match entry {
+                    ActionEntry::Swap { at, first, second } => (
+                        at,
+                        Action::Swap((first[0], first[1]), (second[0], second[1])),
+                    ),
+                    ActionEntry::Undo { at } => (at, Action::Undo),
+                    ActionEntry::Redo { at } => (at, Action::Redo),
+                };
+                TimedAction {
+                    at: Duration::from_nanos(at),
+                    action,
+                }
+            })
+            .collect::<Vec<_>>();
+        // playback makes the actions in order as the clock reaches them, so one out of order or
+        // after the end would never be made, and the replay would never finish
+        let length = Duration::from_nanos(file.length);
+        if actions.windows(2).any(|pair| pair[0].at > pair[1].at) {
+            return Err(ReplayError::Invalid(
+                "the actions aren't in the order they were made".to_string(),
+            ));
+        }
+        if actions.last().is_some_and(|last| last.at > length) {
+            return Err(ReplayError::Invalid(
+                "an action was made after the game ended".to_string(),
+            ));
+        }
+        Ok(Replay {
+            level: file.level,
+            width: file.width,
+            height: file.height,
+            mode,
+            seed: file.seed,
+            actions,
+            length,
+            score: file.score,
+        })
+    }
+
+    pub fn to_toml(&self) -> String {
+        let actions = self
+            .actions
+            .iter()
+            .filter_map(|&TimedAction { at, action }| {
+                let at = nanos(at);
+                match action {
+                    Action::Swap(first, second) => Some(ActionEntry::Swap {
+                        at,
+                        first: [first.0, first.1],
+                        second: [second.0, second.1],
+                    }),
+                    Action::Undo => Some(ActionEntry::Undo { at }),
+                    Action::Redo => Some(ActionEntry::Redo { at }),
+                    // the clock is kept by the times, and pausing doesn't change the game
+                    Action::Tick(_) | Action::Pause | Action::Resume => None,
+                }
+            })
+            .collect();
+        toml::to_string(&ReplayFile {
+            version: REPLAY_VERSION,
+            level: self.level.clone(),
+            width: self.width,
+            height: self.height,
+            mode: self.mode.tag(),
+            seed: self.seed,
+            length: nanos(self.length),
+            score: self.score,
+            actions,
+        })
+        .expect("replays are plain strings and numbers")
+    }
+}
+
+// a replay being played back through a game of its own, as fast as the game clock is moved on
+pub struct Playback {
+    game: GameState,
+    replay: Replay,
+    // the next action to make
+    next: usize,
+    // how far the game clock has been moved on. The game's own clock stops once it is over
+    clock: Duration,
+}
+
+impl Playback {
+    // `level` is the level the replay was played on, see Replay::level
+    pub fn new(replay: Replay, level: Level) -> Self {
+        Playback {
+            game: GameState::with_level(level, replay.seed),
+            replay,
+            next: 0,
+            clock: Duration::ZERO,
+        }
+    }
+
+    pub fn game(&self) -> &GameState {
+        &self.game
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    // move the game clock on by `time`, making every action that falls in it when it was made
+    pub fn advance(&mut self, time: Duration) {
+        let until = (self.clock + time).min(self.replay.length);
+        while let Some(&TimedAction { at, action }) = self
+            .replay
+            .actions
+            .get(self.next)
+            .filter(|next| next.at <= until)
+        {
+            self.tick_to(at);
+            self.game.step(action);
+            self.next += 1;
+        }
+        self.tick_to(until);
+    }
+
+    // play the rest of the replay at once
+    pub fn run_to_end(&mut self) {
+        self.advance(self.replay.length.saturating_sub(self.clock));
+    }
+
+    fn tick_to(&mut self, at: Duration) {
+        if at > self.clock {
+            self.game.step(Action::Tick(at - self.clock));
+            self.clock = at;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.replay.actions.len() && self.clock >= self.replay.length
+    }
+
+    // check the replay played out to the score it recorded. Returns the score if it did
+    pub fn verify(&self) -> Result<usize, ReplayError> {
+        if self.game.score() == self.replay.score {
+            Ok(self.game.score())
+        } else {
+            Err(ReplayError::ScoreMismatch {
+                recorded: self.replay.score,
+                played: self.game.score(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::StepResult;
+
+    // play the best move a few times, taking one back and making it again, until the clock runs
+    // out, recording it all
+    fn recorded_game() -> Replay {
+        // seeds past i64::MAX don't fit in a TOML integer
+        let mut game = GameState::new(8, 8, u64::MAX - 11);
+        let mut replay = Replay::new(&game);
+        for action in [None, None, Some(Action::Undo), Some(Action::Redo), None] {
+            game.step(Action::Tick(Duration::from_millis(1234)));
+            let action = action.unwrap_or_else(|| {
+                let best = game.grid().best_move().unwrap();
+                Action::Swap(best.first, best.second)
+            });
+            assert_ne!(game.step(action), StepResult::Ignored);
+            replay.record(&game, action);
+        }
+        while !game.is_over() {
+            game.step(Action::Tick(Duration::from_millis(16)));
+        }
+        replay.finish(&game);
+        replay
+    }
+
+    #[test]
+    fn replays_play_back_to_the_same_score() {
+        let replay = recorded_game();
+        assert!(replay.score > 0);
+        let level = replay.level(&[]).unwrap();
+
+        // played back a frame at a time, the same as in one go
+        let mut playback = Playback::new(replay.clone(), level);
+        while !playback.is_finished() {
+            playback.advance(Duration::from_millis(100));
+        }
+        assert!(playback.game().is_over());
+        assert_eq!(playback.verify().unwrap(), replay.score);
+
+        let mut tampered = replay.clone();
+        tampered.score += 1;
+        let mut playback = Playback::new(tampered, replay.level(&[]).unwrap());
+        playback.run_to_end();
+        assert!(matches!(
+            playback.verify(),
+            Err(ReplayError::ScoreMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn replays_are_read_back_from_their_file() {
+        let replay = recorded_game();
+        let text = replay.to_toml();
+        assert_eq!(Replay::from_toml(&text).unwrap(), replay);
+
+        let newer = text.replace("version = 1", "version = 2");
+        assert!(matches!(
+            Replay::from_toml(&newer),
+            Err(ReplayError::Version(2))
+        ));
+        let mut late = replay.clone();
+        late.length = late.actions[3].at;
+        assert!(matches!(
+            Replay::from_toml(&late.to_toml()),
+            Err(ReplayError::Invalid(_))
+        ));
+        let mut unsorted = replay.clone();
+        unsorted.actions.swap(0, 1);
+        assert!(matches!(
+            Replay::from_toml(&unsorted.to_toml()),
+            Err(ReplayError::Invalid(_))
+        ));
+        for (side, size) in [("width", 2), ("height", 0), ("width", 51)] {
+            let resized = text.replace(&format!("{} = 8", side), &format!("{} = {}", side, size));
+            assert!(matches!(
+                Replay::from_toml(&resized),
+                Err(ReplayError::Invalid(_))
+            ));
+        }
+        let unknown = text.replace("level = \"Classic\"", "level = \"Nowhere\"");
+        assert!(matches!(
+            Replay::from_toml(&unknown).unwrap().level(&[]),
+            Err(ReplayError::UnknownLevel(_))
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+// A game part way through, written out as TOML so it can be picked back up later:
+//
+//     version = 1
+//     seed = "1234"            # a string, since seeds can be too big for a TOML integer
+//     score = 420
+//     elapsed = 12000000000    # how much of the game clock has run, in nanoseconds
+//     bonus = 0                # the time or moves bonus, once the game is over
+//
+//     [outcome.Won]            # only there once the game is over
+//     stars = 2
+//
+//     [rng]                    # where the random number generator was up to
+//     key = "0a1b..."          # 32 bytes in hex
+//     stream = "0"
+//     word_pos = "1536"
+//
+//     [level]                  # the level as it is being played, board and all
+//     name = "Classic"
+//     mode = "timed:45"        # as GameMode::tag writes it
+//     ...
+//
+//     [progress]
+//     ...
+//
+//     [grid]                   # every space on the board, row by row from the top
+//     ...
+//
+// Only what is needed to go on playing is saved, so nothing made before the save can be undone
+// after it.
+use crate::file_version::read_version;
+use crate::game::GameState;
+use crate::grid::GameGrid;
+use crate::grid_generator::GameRng;
+use crate::level::{GameMode, Level, Objective, Outcome, Progress};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+// the version of the file written now. Files from any other version are refused, since the board
+// or rules they were saved under may have changed
+pub const SAVE_VERSION: u32 = 1;
+
+// why a saved game couldn't be restored
+#[derive(Debug)]
+pub enum SaveError {
+    Parse(toml::de::Error),
+    // the file was written by another version of the game
+    Version(u32),
+    // the file parsed but doesn't describe a game that can be played
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Parse(error) => write!(f, "{}", error),
+            SaveError::Version(version) => write!(
+                f,
+                "the game was saved by version {}, but only version {} can be resumed",
+                version, SAVE_VERSION
+            ),
+            SaveError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+fn invalid(message: impl Into<String>) -> SaveError {
+    SaveError::Invalid(message.into())
+}
+
+// the random number generator's state. The numbers are strings, since they can be too big for a
+// TOML integer
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RngState {
+    key: String,
+    stream: String,
+    word_pos: String,
+}
+
+impl RngState {
+    fn of(rng: &GameRng) -> Self {
+        RngState {
+            key: rng
+                .get_seed()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            stream: rng.get_stream().to_string(),
+            word_pos: rng.get_word_pos().to_string(),
+        }
+    }
+
+    fn restore(&self) -> Result<GameRng, SaveError> {
+        use rand::SeedableRng;
+        let bad = || invalid("the random number generator's state is corrupt");
+        if self.key.len() != 64 || !self.key.is_ascii() {
+            return Err(bad());
+        }
+        let mut key = [0; 32];
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte =
+                u8::from_str_radix(&self.key[2 * index..2 * index + 2], 16).map_err(|_| bad())?;
+        }
+        let mut rng = GameRng::from_seed(key);
+        rng.set_stream(self.stream.parse().map_err(|_| bad())?);
+        rng.set_word_pos(self.word_pos.parse().map_err(|_| bad())?);
+        Ok(rng)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelState {
+    name: String,
+    mode: String,
+    stars: [usize; 3],
+    objectives: Vec<Objective>,
+    // the board the level started from, for playing it again
+    board: GameGrid,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SaveFile {
+    version: u32,
+    #[serde(with = "crate::u64_string")]
+    seed: u64,
+    score: usize,
+    elapsed: u64,
+    bonus: usize,
+    outcome: Option<Outcome>,
+    rng: RngState,
+    level: LevelState,
+    progress: Progress,
+    grid: GameGrid,
+}
+
+impl GameState {
+    // the game as it is now, ready to be written to disk. A game that is over comes back over,
+    // with the same outcome and bonus
+    pub fn to_save(&self) -> String {
+        let level = self.level();
+        toml::to_string(&SaveFile {
+            version: SAVE_VERSION,
+            seed: self.seed(),
+            score: self.score(),
+            elapsed: self.elapsed().as_nanos().try_into().unwrap_or(u64::MAX),
+            bonus: self.bonus(),
+            outcome: self.outcome(),
+            rng: RngState::of(self.rng()),
+            level: LevelState {
+                name: level.name.clone(),
+                mode: level.mode.tag(),
+                stars: level.stars,
+                objectives: level.objectives.clone(),
+                board: level.board.clone(),
+            },
+            progress: self.progress().clone(),
+            grid: self.grid().clone(),
+        })
+        .expect("saved games are plain strings and numbers")
+    }
+
+    // pick up a game saved by to_save where it left off
+    pub fn from_save(text: &str) -> Result<GameState, SaveError> {
+        let version = read_version(text).map_err(SaveError::Parse)?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version(version));
+        }
+        let file: SaveFile = toml::from_str(text).map_err(SaveError::Parse)?;
+
+        let mode = GameMode::parse(&file.level.mode)
+            .ok_or_else(|| invalid(format!("{} isn't a mode", file.level.mode)))?;
+        // a board that doesn't hold together would panic the first time it was touched
+        for (what, board) in [("board", &file.grid), ("level's board", &file.level.board)] {
+            if !board.is_well_formed() {
+                return Err(invalid(format!("the {} is corrupt", what)));
+            }
+        }
+        if (file.grid.width(), file.grid.height())
+            != (file.level.board.width(), file.level.board.height())
+        {
+            return Err(invalid("the board isn't the size of the level's board"));
+        }
+
+        let level = Level {
+            name: file.level.name,
+            board: file.level.board,
+            objectives: file.level.objectives,
+            mode,
+            stars: file.level.stars,
+        };
+        let mut game = GameState::resumed(
+            level,
+            file.grid,
+            file.rng.restore()?,
+            file.seed,
+            file.score,
+            file.progress,
+            Duration::from_nanos(file.elapsed),
+        );
+        game.set_ending(file.outcome, file.bonus);
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Action, StepResult};
+    use crate::grid::{CandyColor, CANDY_COLORS};
+
+    #[test]
+    fn a_saved_game_goes_on_as_it_would_have() {
+        let mut level = Level::classic(6, 6, GameMode::Moves(10));
+        level.board.set_jelly(2, 2, 2);
+        level.board.make_hole(0, 0);
+        level.board.fill_space(5, 5, CandyColor::Stone);
+        level.objectives = vec![
+            Objective::ClearJelly,
+            Objective::Collect {
+                color: CandyColor::White,
+                count: 5,
+            },
+        ];
+        let mut game = GameState::with_level(level, u64::MAX - 3);
+        game.step(Action::Tick(Duration::from_millis(1500)));
+        let best = game.grid().best_move().unwrap();
+        game.step(Action::Swap(best.first, best.second));
+
+        let mut resumed = GameState::from_save(&game.to_save()).unwrap();
+        assert!(resumed.grid() == game.grid());
+        assert_eq!(resumed.score(), game.score());
+        assert_eq!(resumed.elapsed(), game.elapsed());
+        assert_eq!(resumed.progress(), game.progress());
+        assert_eq!(resumed.level().objectives, game.level().objectives);
+        assert_eq!(resumed.moves_left(), Some(9));
+
+        // the refills after the save come out the same, since the RNG picks up where it was
+        let best = game.grid().best_move().unwrap();
+        let played = game.step(Action::Swap(best.first, best.second));
+        assert!(matches!(played, StepResult::Swapped { .. }));
+        assert_eq!(resumed.step(Action::Swap(best.first, best.second)), played);
+        assert!(resumed.grid() == game.grid());
+    }
+
+    #[test]
+    fn a_finished_game_comes_back_finished() {
+        let mut level = Level::classic(6, 6, GameMode::Timed(Duration::from_secs(5)));
+        level.objectives = vec![Objective::Score(1000)];
+        let mut game = GameState::with_level(level, 7);
+        game.step(Action::Tick(Duration::from_secs(6)));
+        assert_eq!(game.outcome(), Some(Outcome::Lost));
+
+        let resumed = GameState::from_save(&game.to_save()).unwrap();
+        assert!(resumed.is_over());
+        assert_eq!(resumed.outcome(), game.outcome());
+        assert_eq!(resumed.bonus(), game.bonus());
+        assert_eq!(resumed.score(), game.score());
+    }
+
+    #[test]
+    fn saves_from_other_versions_or_damaged_ones_are_refused() {
+        let text = GameState::new(5, 5, 1).to_save();
+        let older = text.replace("version = 1", "version = 0");
+        assert!(matches!(
+            GameState::from_save(&older),
+            Err(SaveError::Version(0))
+        ));
+        assert!(matches!(
+            GameState::from_save("version = 1"),
+            Err(SaveError::Parse(_))
+        ));
+        let damaged = text.replace("width = 5", "width = 7");
+        assert!(matches!(
+            GameState::from_save(&damaged),
+            Err(SaveError::Invalid(_))
+        ));
+        // with one color there would be no refilling the board without it matching forever
+        let every_color = CANDY_COLORS
+            .map(|color| format!("\"{:?}\"", color))
+            .join(", ");
+        let one_color = text.replace(&every_color, "\"White\", \"White\", \"White\"");
+        assert_ne!(one_color, text);
+        assert!(matches!(
+            GameState::from_save(&one_color),
+            Err(SaveError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::grid::{Clear, Match, Orientation, Special};
+use std::fmt;
+use std::time::Duration;
+
+// points for one match, by its length and shape. Crosses count as much as a line of five since
+// they make a wrapped candy
+pub fn match_points(found: &Match) -> usize {
+    match (found.orientation, found.length) {
+        (Orientation::Cross, _) => 200,
+        (_, 3) => 60,
+        (_, 4) => 120,
+        // the longest line is worth 40 more for every candy past five
+        (_, length) => 200 + 40 * length.saturating_sub(5),
+    }
+}
+
+// the bonus for a special candy going off, on top of the points for what it clears
+pub fn detonation_points(special: Special) -> usize {
+    match special {
+        Special::None => 0,
+        Special::StripedHorizontal | Special::StripedVertical => 120,
+        Special::Wrapped => 180,
+        Special::ColorBomb => 300,
+    }
+}
+
+// points for every candy a detonation blacks out
+pub const BLASTED_CANDY_POINTS: usize = 20;
+
+// points for every whole second left on the clock when a game ends early
+pub const TIME_BONUS_PER_SECOND: usize = 50;
+
+pub fn time_bonus(time_left: Duration) -> usize {
+    TIME_BONUS_PER_SECOND * time_left.as_secs() as usize
+}
+
+// points for every move left unused when a game ends early
+pub const MOVE_BONUS_PER_MOVE: usize = 150;
+
+pub fn move_bonus(moves_left: usize) -> usize {
+    MOVE_BONUS_PER_MOVE * moves_left
+}
+
+// the points for one wave of clears: the first is the swap itself, and each one after is a chain
+// match made by the candies that fell into place
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CascadeScore {
+    // 1 for the swap, 2 for the first chain and so on
+    pub depth: usize,
+    pub match_points: usize,
+    // the detonation bonuses plus the points for the candies the detonations blacked out
+    pub detonation_points: usize,
+    // what the wave is multiplied by, which grows with the depth
+    pub multiplier: usize,
+    pub points: usize,
+}
+
+// how one move scored, wave by wave
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MoveScore {
+    pub cascades: Vec<CascadeScore>,
+    pub total: usize,
+}
+
+impl MoveScore {
+    // score the next wave of clears the move caused
+    pub fn add(&mut self, clear: &Clear) {
+        let depth = self.cascades.len() + 1;
+        let match_points: usize = clear.matches.iter().map(match_points).sum();
+        let detonation_points = clear
+            .detonations
+            .iter()
+            .map(|&(_, special)| detonation_points(special))
+            .sum::<usize>()
+            + clear.blasted * BLASTED_CANDY_POINTS;
+
+        let multiplier = depth;
+        let points = (match_points + detonation_points) * multiplier;
+        self.total += points;
+        self.cascades.push(CascadeScore {
+            depth,
+            match_points,
+            detonation_points,
+            multiplier,
+            points,
+        });
+    }
+}
+
+impl fmt::Display for MoveScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} points", self.total)?;
+        for cascade in &self.cascades {
+            write!(
+                f,
+                "\n  {}: {} for matches + {} for specials, x{} = {}",
+                if cascade.depth == 1 {
+                    "swap".to_string()
+                } else {
+                    format!("chain {}", cascade.depth - 1)
+                },
+                cascade.match_points,
+                cascade.detonation_points,
+                cascade.multiplier,
+                cascade.points
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::CandyColor;
+
+    fn line(length: usize) -> Match {
+        Match {
+            color: CandyColor::White,
+            orientation: Orientation::Horizontal,
+            length,
+            cells: (0..length).map(|x| (x, 0)).collect(),
+        }
+    }
+
+    #[test]
+    fn longer_matches_score_more() {
+        assert!(match_points(&line(3)) < match_points(&line(4)));
+        assert!(match_points(&line(4)) < match_points(&line(5)));
+        assert!(match_points(&line(5)) < match_points(&line(6)));
+    }
+
+    #[test]
+    fn chains_are_multiplied_by_depth() {
+        let clear = Clear {
+            matches: vec![line(3)],
+            cleared: 3,
+            ..Clear::default()
+        };
+        let mut score = MoveScore::default();
+        score.add(&clear);
+        score.add(&clear);
+        assert_eq!(score.cascades[0].points, 60);
+        assert_eq!(score.cascades[1].points, 120);
+        assert_eq!(score.total, 180);
+    }
+
+    #[test]
+    fn detonations_score_their_bonus_and_what_they_blast() {
+        // a striped candy in a line of three goes off and takes four more candies with it
+        let clear = Clear {
+            matches: vec![line(3)],
+            detonations: vec![((1, 0), Special::StripedVertical)],
+            cleared: 7,
+            blasted: 4,
+            ..Clear::default()
+        };
+        let mut score = MoveScore::default();
+        score.add(&clear);
+        assert_eq!(score.cascades[0].match_points, 60);
+        assert_eq!(
+            score.cascades[0].detonation_points,
+            120 + 4 * BLASTED_CANDY_POINTS
+        );
+    }
+
+    #[test]
+    fn chocolate_broken_by_a_match_is_no_detonation() {
+        let clear = Clear {
+            matches: vec![line(3)],
+            cleared: 4,
+            chocolate: 1,
+            ..Clear::default()
+        };
+        let mut score = MoveScore::default();
+        score.add(&clear);
+        assert_eq!(score.cascades[0].detonation_points, 0);
+        assert_eq!(score.total, 60);
+    }
+}
@@ -0,0 +1,48 @@
+use super::menu::Menu;
+use super::playing::Playing;
+use super::{Context, Frame, Screen, Transition};
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+// a menu of every level, then a way back to the title
+pub struct LevelSelect {
+    menu: Menu,
+    levels: usize,
+}
+
+impl LevelSelect {
+    pub fn new(ctx: &Context) -> Self {
+        let mut items: Vec<String> = ctx
+            .levels
+            .iter()
+            .map(|level| level.name.to_uppercase())
+            .collect();
+        items.push("BACK".to_string());
+        LevelSelect {
+            menu: Menu::new(items),
+            levels: ctx.levels.len(),
+        }
+    }
+}
+
+impl Screen for LevelSelect {
+    fn update(&mut self, ctx: &mut Context, _frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) {
+            return Transition::Pop;
+        }
+        match self.menu.update(ctx, ctx.view_rect()) {
+            // the game takes the level select's place, so leaving it goes back to the title
+            Some(index) if index < self.levels => {
+                let level = ctx.settings.apply(&ctx.levels[index]);
+                let seed = ctx.settings.take_seed();
+                Transition::Switch(Box::new(Playing::new(level, seed)))
+            }
+            Some(_) => Transition::Pop,
+            None => Transition::Stay,
+        }
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        self.menu.render(frame, ctx.view_rect());
+    }
+}
@@ -0,0 +1,91 @@
+use super::{Context, Frame};
+use crate::font::{text_width, Align};
+use scene2d::grid::CELL_SIZE;
+use winit::event::VirtualKeyCode as Key;
+
+// how big menu text is, unless the longest item needs it smaller to fit
+const TEXT_SIZE: f32 = CELL_SIZE / 2.0;
+
+// a column of items, one of them picked out, chosen with the arrow keys and Enter or by clicking
+pub struct Menu {
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(items: Vec<String>) -> Self {
+        Menu { items, selected: 0 }
+    }
+
+    pub fn set_item(&mut self, index: usize, item: String) {
+        self.items[index] = item;
+    }
+
+    // the item chosen this frame, if any. `area` is the part of the world the menu is centered in,
+    // as [x, y, width, height]
+    pub fn update(&mut self, ctx: &Context, area: [f32; 4]) -> Option<usize> {
+        let last = self.items.len() - 1;
+        if ctx.key_pressed(Key::Up) || ctx.key_pressed(Key::W) {
+            self.selected = self.selected.checked_sub(1).unwrap_or(last);
+        }
+        if ctx.key_pressed(Key::Down) || ctx.key_pressed(Key::S) {
+            self.selected = if self.selected == last {
+                0
+            } else {
+                self.selected + 1
+            };
+        }
+        if ctx.key_pressed(Key::Return) || ctx.key_pressed(Key::Space) {
+            return Some(self.selected);
+        }
+
+        let [mouse_x, mouse_y] = ctx.mouse;
+        let hovered = self.layout(area).position(|[x, y, width, height]| {
+            mouse_x >= x && mouse_x < x + width && mouse_y >= y && mouse_y < y + height
+        })?;
+        // the mouse only picks out an item when it moves, so it doesn't fight the arrow keys
+        let delta = ctx.input.mouse_delta();
+        if delta.x != 0.0 || delta.y != 0.0 {
+            self.selected = hovered;
+        }
+        ctx.clicked().then_some(hovered)
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: [f32; 4]) {
+        let size = self.text_size(area);
+        for (index, (item, [x, y, width, _])) in
+            self.items.iter().zip(self.layout(area)).enumerate()
+        {
+            // the selected item is pointed at from both sides
+            let text = if index == self.selected {
+                format!(">{}<", item)
+            } else {
+                item.clone()
+            };
+            frame.text(&text, [x + width / 2.0, y], size, Align::Center);
+        }
+    }
+
+    fn text_size(&self, area: [f32; 4]) -> f32 {
+        let longest = self.items.iter().map(|item| item.len()).max().unwrap_or(0);
+        // room for the markers either side of the selected item
+        TEXT_SIZE.min(area[2] / (longest + 2) as f32)
+    }
+
+    // where each item is, as [x, y, width, height], from the top down
+    fn layout(&self, area: [f32; 4]) -> impl Iterator<Item = [f32; 4]> + '_ {
+        let [left, bottom, width, height] = area;
+        let size = self.text_size(area);
+        let line_height = 2.0 * size;
+        let top = bottom + (height + self.items.len() as f32 * line_height) / 2.0;
+        self.items.iter().enumerate().map(move |(index, item)| {
+            let item_width = text_width(item, size);
+            [
+                left + (width - item_width) / 2.0,
+                top - (index + 1) as f32 * line_height + size / 2.0,
+                item_width,
+                size,
+            ]
+        })
+    }
+}
@@ -0,0 +1,389 @@
+// The screens the app moves between: the title, the level select, the settings, the game itself
+// and the pause and results screens shown over it. They sit on a stack, so a screen pushed over
+// another hands control back to it when it is popped
+use crate::font::{text_sprites, Align};
+use crate::hud::HUD_HEIGHT;
+use crate::input::Input;
+use crate::GPUSprite;
+use scene2d::grid::BOARD_ORIGIN;
+use scene2d::high_scores::HighScores;
+use scene2d::{GameMode, GameState, Level};
+use std::time::Duration;
+
+mod level_select;
+mod menu;
+mod paused;
+mod playing;
+mod replaying;
+mod results;
+mod settings;
+mod title;
+
+pub use playing::Playing;
+pub use replaying::Replaying;
+pub use title::Title;
+
+// the region of the sprite sheet drawn over the board to dim it under the screens shown over a game
+const SHADE: [f32; 4] = [64.0 / 80.0, 128.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+
+// the sprites to draw this frame. Text is drawn from the glyph atlas after everything from the
+// sprite sheet, so it always ends up on top
+#[derive(Default)]
+pub struct Frame {
+    sheet: Vec<GPUSprite>,
+    text: Vec<GPUSprite>,
+}
+
+impl Frame {
+    pub fn clear(&mut self) {
+        self.sheet.clear();
+        self.text.clear();
+    }
+
+    // a sprite from the sprite sheet, with its region of the world as [x, y, width, height]
+    pub fn sprite(&mut self, screen_region: [f32; 4], sheet_region: [f32; 4]) {
+        self.sheet.push(GPUSprite {
+            screen_region,
+            sheet_region,
+        });
+    }
+
+    // a line of text, with `pos` at its left edge, middle or right edge
+    pub fn text(&mut self, text: &str, pos: [f32; 2], size: f32, align: Align) {
+        self.text.extend(text_sprites(text, pos, size, align));
+    }
+
+    pub fn sheet_sprites(&self) -> &[GPUSprite] {
+        &self.sheet
+    }
+
+    pub fn text_sprites(&self) -> &[GPUSprite] {
+        &self.text
+    }
+}
+
+// what the player can change from the settings screen. They apply to the next game started
+pub struct Settings {
+    // the mode every game is played in, or None to play each level in its own
+    pub mode: Option<GameMode>,
+    // how long the player can go without clicking before a move is pointed out, or None for no
+    // hints
+    pub hint_delay: Option<Duration>,
+    // the seed for the next game, if it was given on the command line. Later games get a random
+    // one
+    pub seed: Option<u64>,
+    // the name high scores are saved under
+    pub player: String,
+}
+
+impl Settings {
+    // a level as it will be played with these settings
+    pub fn apply(&self, level: &Level) -> Level {
+        let mut level = level.clone();
+        if let Some(mode) = self.mode {
+            level.mode = mode;
+        }
+        level
+    }
+
+    pub fn take_seed(&mut self) -> u64 {
+        self.seed.take().unwrap_or_else(rand::random)
+    }
+}
+
+// what every screen can see and change
+pub struct Context {
+    pub input: Input,
+    // where the mouse is in world units
+    pub mouse: [f32; 2],
+    // the size of the part of the world the camera has to show, from BOARD_ORIGIN. The game sets
+    // it to its board and HUD, and the menus lay themselves out in it
+    pub view: [f32; 2],
+    pub title: String,
+    pub settings: Settings,
+    // the levels that can be picked from the level select, the classic game first
+    pub levels: Vec<Level>,
+    // the best scores of every level and mode, as saved so far
+    pub high_scores: HighScores,
+    // a game left part way through, ready to go on with
+    pub saved_game: Option<GameState>,
+}
+
+impl Context {
+    pub fn new(view: [f32; 2], settings: Settings, levels: Vec<Level>) -> Self {
+        Context {
+            input: Input::default(),
+            mouse: [0.0, 0.0],
+            view,
+            title: "candycrunch".to_string(),
+            settings,
+            levels,
+            high_scores: HighScores::default(),
+            saved_game: None,
+        }
+    }
+
+    // the part of the world the camera shows, as [x, y, width, height]
+    pub fn view_rect(&self) -> [f32; 4] {
+        [BOARD_ORIGIN[0], BOARD_ORIGIN[1], self.view[0], self.view[1]]
+    }
+
+    // the board, which is the view without the HUD above it, as [x, y, width, height]
+    pub fn board_rect(&self) -> [f32; 4] {
+        let [x, y, width, height] = self.view_rect();
+        [x, y, width, height - HUD_HEIGHT]
+    }
+
+    pub fn clicked(&self) -> bool {
+        self.input
+            .is_mouse_released(winit::event::MouseButton::Left)
+    }
+
+    pub fn key_pressed(&self, key: winit::event::VirtualKeyCode) -> bool {
+        self.input.is_key_pressed(key)
+    }
+}
+
+// what the top screen wants done with the stack after a frame
+pub enum Transition {
+    Stay,
+    // put a screen on top
+    Push(Box<dyn Screen>),
+    // take the top screen off, handing control back to the one under it
+    Pop,
+    // replace the top screen
+    Switch(Box<dyn Screen>),
+    // replace the whole stack, from the bottom up
+    Reset(Vec<Box<dyn Screen>>),
+    Quit,
+}
+
+pub trait Screen {
+    // called when the screen is put on the stack, and when it is taken off
+    fn enter(&mut self, _ctx: &mut Context) {}
+    fn exit(&mut self, _ctx: &mut Context) {}
+
+    // called when another screen is pushed over this one, and when that screen is popped
+    fn cover(&mut self, _ctx: &mut Context) {}
+    fn uncover(&mut self, _ctx: &mut Context) {}
+
+    // the window lost focus while this screen was on top
+    fn focus_lost(&mut self, _ctx: &mut Context) -> Transition {
+        Transition::Stay
+    }
+
+    // run one frame. Only the top screen is updated
+    fn update(&mut self, ctx: &mut Context, frame_time: Duration) -> Transition;
+
+    // draw the screen. The screens under the top one are drawn first, from the highest one that
+    // hides everything under it
+    fn render(&self, ctx: &Context, frame: &mut Frame);
+
+    // whether the screen hides everything under it
+    fn is_opaque(&self) -> bool {
+        true
+    }
+}
+
+pub struct ScreenStack {
+    screens: Vec<Box<dyn Screen>>,
+}
+
+impl ScreenStack {
+    // start with the given screens, the last one on top
+    pub fn new(screens: Vec<Box<dyn Screen>>, ctx: &mut Context) -> Self {
+        let mut stack = ScreenStack {
+            screens: Vec::new(),
+        };
+        stack.apply(Transition::Reset(screens), ctx);
+        stack
+    }
+
+    // run the top screen for one frame. Returns false once the app should close
+    pub fn update(&mut self, ctx: &mut Context, frame_time: Duration) -> bool {
+        let transition = match self.screens.last_mut() {
+            Some(screen) => screen.update(ctx, frame_time),
+            None => return false,
+        };
+        self.apply(transition, ctx)
+    }
+
+    pub fn focus_lost(&mut self, ctx: &mut Context) -> bool {
+        let transition = match self.screens.last_mut() {
+            Some(screen) => screen.focus_lost(ctx),
+            None => return false,
+        };
+        self.apply(transition, ctx)
+    }
+
+    pub fn render(&self, ctx: &Context, frame: &mut Frame) {
+        let bottom = self
+            .screens
+            .iter()
+            .rposition(|screen| screen.is_opaque())
+            .unwrap_or(0);
+        for screen in &self.screens[bottom..] {
+            screen.render(ctx, frame);
+        }
+    }
+
+    // take every screen off the stack, as when the window is closed
+    pub fn close(&mut self, ctx: &mut Context) {
+        self.apply(Transition::Quit, ctx);
+    }
+
+    fn push(&mut self, mut screen: Box<dyn Screen>, ctx: &mut Context) {
+        if let Some(top) = self.screens.last_mut() {
+            top.cover(ctx);
+        }
+        screen.enter(ctx);
+        self.screens.push(screen);
+    }
+
+    fn pop(&mut self, ctx: &mut Context) {
+        if let Some(mut top) = self.screens.pop() {
+            top.exit(ctx);
+        }
+    }
+
+    fn apply(&mut self, transition: Transition, ctx: &mut Context) -> bool {
+        match transition {
+            Transition::Stay => {}
+            Transition::Push(screen) => self.push(screen, ctx),
+            Transition::Pop => {
+                self.pop(ctx);
+                if let Some(top) = self.screens.last_mut() {
+                    top.uncover(ctx);
+                }
+            }
+            // the screen under the one switched out stays covered, so it isn't told
+            Transition::Switch(mut screen) => {
+                self.pop(ctx);
+                screen.enter(ctx);
+                self.screens.push(screen);
+            }
+            Transition::Reset(screens) => {
+                while !self.screens.is_empty() {
+                    self.pop(ctx);
+                }
+                for screen in screens {
+                    self.push(screen, ctx);
+                }
+            }
+            Transition::Quit => {
+                while !self.screens.is_empty() {
+                    self.pop(ctx);
+                }
+            }
+        }
+        !self.screens.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // a screen that writes down every hook called on it, and does what it is told on update
+    struct Probe {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        next: Option<Transition>,
+    }
+
+    impl Probe {
+        fn new(name: &'static str, log: &Rc<RefCell<Vec<String>>>) -> Box<Self> {
+            Box::new(Probe {
+                name,
+                log: log.clone(),
+                next: None,
+            })
+        }
+
+        fn then(mut self: Box<Self>, next: Transition) -> Box<Self> {
+            self.next = Some(next);
+            self
+        }
+
+        fn note(&self, hook: &str) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, hook));
+        }
+    }
+
+    impl Screen for Probe {
+        fn enter(&mut self, _ctx: &mut Context) {
+            self.note("enter");
+        }
+        fn exit(&mut self, _ctx: &mut Context) {
+            self.note("exit");
+        }
+        fn cover(&mut self, _ctx: &mut Context) {
+            self.note("cover");
+        }
+        fn uncover(&mut self, _ctx: &mut Context) {
+            self.note("uncover");
+        }
+        fn update(&mut self, _ctx: &mut Context, _frame_time: Duration) -> Transition {
+            self.next.take().unwrap_or(Transition::Stay)
+        }
+        fn render(&self, _ctx: &Context, _frame: &mut Frame) {}
+    }
+
+    fn context() -> Context {
+        let settings = Settings {
+            mode: None,
+            hint_delay: None,
+            seed: None,
+            player: "test".to_string(),
+        };
+        Context::new([24.0, 24.0], settings, Vec::new())
+    }
+
+    #[test]
+    fn screens_are_told_when_they_are_covered_and_uncovered() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut ctx = context();
+        let game = Probe::new("game", &log).then(Transition::Push(
+            Probe::new("pause", &log).then(Transition::Pop),
+        ));
+        let mut stack = ScreenStack::new(vec![game], &mut ctx);
+
+        assert!(stack.update(&mut ctx, Duration::ZERO));
+        assert!(stack.update(&mut ctx, Duration::ZERO));
+        assert_eq!(
+            *log.borrow(),
+            [
+                "game enter",
+                "game cover",
+                "pause enter",
+                "pause exit",
+                "game uncover"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_app_closes_once_the_stack_is_empty() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut ctx = context();
+        let title = Probe::new("title", &log);
+        let game = Probe::new("game", &log).then(Transition::Quit);
+        let mut stack = ScreenStack::new(vec![title, game], &mut ctx);
+
+        assert!(!stack.update(&mut ctx, Duration::ZERO));
+        assert_eq!(
+            *log.borrow(),
+            [
+                "title enter",
+                "title cover",
+                "game enter",
+                "game exit",
+                "title exit"
+            ]
+        );
+    }
+}
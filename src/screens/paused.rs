@@ -0,0 +1,68 @@
+use super::menu::Menu;
+use super::title::Title;
+use super::{Context, Frame, Screen, Transition, SHADE};
+use scene2d::grid::CELL_SIZE;
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+const PAUSE_SIGN: [f32; 4] = [0.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+
+const RESUME: usize = 0;
+
+// shown over the game while its clock is stopped. The board is covered, so it can't be studied
+// with the clock stopped, and the HUD above it is left showing
+pub struct Paused {
+    menu: Menu,
+}
+
+impl Paused {
+    pub fn new() -> Self {
+        Paused {
+            menu: Menu::new(vec!["RESUME".to_string(), "MENU".to_string()]),
+        }
+    }
+
+    // the menu goes in the bottom half of the board, under the pause sign
+    fn menu_area(ctx: &Context) -> [f32; 4] {
+        let [x, y, width, height] = ctx.board_rect();
+        [x, y, width, height / 2.0]
+    }
+}
+
+impl Screen for Paused {
+    fn enter(&mut self, _ctx: &mut Context) {
+        println!("Paused. Press Escape or P to go on.");
+    }
+
+    fn update(&mut self, ctx: &mut Context, _frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) || ctx.key_pressed(Key::P) {
+            return Transition::Pop;
+        }
+        match self.menu.update(ctx, Paused::menu_area(ctx)) {
+            Some(RESUME) => Transition::Pop,
+            Some(_) => Transition::Reset(vec![Box::new(Title::new())]),
+            None => Transition::Stay,
+        }
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        let board = ctx.board_rect();
+        frame.sprite(board, SHADE);
+        let [x, y, width, height] = board;
+        let size = (2.0 * CELL_SIZE).min(height / 4.0);
+        frame.sprite(
+            [
+                x + (width - size) / 2.0,
+                y + height * 3.0 / 4.0 - size / 2.0,
+                size,
+                size,
+            ],
+            PAUSE_SIGN,
+        );
+        self.menu.render(frame, Paused::menu_area(ctx));
+    }
+
+    fn is_opaque(&self) -> bool {
+        false
+    }
+}
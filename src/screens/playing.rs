@@ -0,0 +1,417 @@
+use super::paused::Paused;
+use super::results::{Results, ResultsScreen};
+use super::{Context, Frame, Screen, Transition};
+use crate::hud::{self, HUD_HEIGHT};
+use crate::storage;
+use scene2d::grid::{CandyColor, GameGrid, Move, CELL_SIZE};
+use scene2d::high_scores::{date_string, HighScore};
+use scene2d::replay::Replay;
+use scene2d::{Action, GameMode, GameState, Level, Outcome, StepResult};
+use std::time::{Duration, Instant};
+use winit::event::VirtualKeyCode as Key;
+
+// how long a rejected swap takes to slide over and back
+const BOUNCE_DURATION: Duration = Duration::from_millis(250);
+
+// how fast the hint markers pulse, in radians per second
+const HINT_PULSE_SPEED: f32 = 6.0;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const SELECTOR: [f32; 4] = [0.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const HINT_MARKER: [f32; 4] = [16.0 / 80.0, 112.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+
+// looks up the region of the sprite sheet one layer of the board draws for the space at (x, y)
+type LayerCoords = fn(&GameGrid, usize, usize) -> [f32; 4];
+
+// what each layer of the board draws for a space, from the bottom up: the candy, the jelly over
+// it, then any blocker covering it
+const BOARD_LAYERS: [LayerCoords; 3] = [
+    GameGrid::get_color_coords,
+    GameGrid::get_jelly_coords,
+    GameGrid::get_blocker_coords,
+];
+
+// a swap that didn't make a match, being shown bouncing back
+#[derive(Clone, Copy)]
+struct Bounce {
+    start: Instant,
+    first: (usize, usize),
+    second: (usize, usize),
+}
+
+// a game being played, with the HUD above the board
+pub struct Playing {
+    game: GameState,
+    // every move made, written out once the game is over. A resumed game isn't recorded, since
+    // its replay would have to start part way through
+    replay: Option<Replay>,
+    // the space clicked first, waiting for a second click to swap it with
+    selected: Option<(usize, usize)>,
+    hint: Option<Move>,
+    bounce: Option<Bounce>,
+    // when the player last did something, for the hint
+    last_action: Instant,
+    // when the game was started, for the hint markers' pulse
+    start_time: Instant,
+}
+
+impl Playing {
+    // a game of `level` as the settings have it, with the board and every refill coming from the
+    // seed
+    pub fn new(level: Level, seed: u64) -> Self {
+        let game = GameState::with_level(level, seed);
+        let replay = Replay::new(&game);
+        Playing::with_game(game, Some(replay))
+    }
+
+    // go on with a game that was saved part way through
+    pub fn resumed(mut game: GameState) -> Self {
+        // it may have been saved while something was shown over it
+        game.step(Action::Resume);
+        Playing::with_game(game, None)
+    }
+
+    fn with_game(game: GameState, replay: Option<Replay>) -> Self {
+        let now = Instant::now();
+        Playing {
+            game,
+            replay,
+            selected: None,
+            hint: None,
+            bounce: None,
+            last_action: now,
+            start_time: now,
+        }
+    }
+
+    fn pause(&self) -> Transition {
+        Transition::Push(Box::new(Paused::new()))
+    }
+
+    // the player did something, so put away the hint and start waiting for the next one
+    fn act(&mut self) {
+        self.last_action = Instant::now();
+        self.hint = None;
+    }
+
+    fn click(&mut self, ctx: &Context) {
+        let [world_x, world_y] = ctx.mouse;
+        let Some(clicked) = self.game.grid().screen_to_grid(world_x, world_y) else {
+            return;
+        };
+        let Some(first) = self.selected.take() else {
+            self.selected = Some(clicked);
+            return;
+        };
+        let swap = Action::Swap(clicked, first);
+        match self.game.step(swap) {
+            // only swap candies that are one apart
+            StepResult::Invalid => {
+                println!("Invalid click! Can only swap uncovered candies one apart.")
+            }
+            // show the candies trying to swap and bouncing back
+            StepResult::Rejected => {
+                self.bounce = Some(Bounce {
+                    start: Instant::now(),
+                    first: clicked,
+                    second: first,
+                })
+            }
+            StepResult::Swapped { shuffled, .. } => {
+                if let Some(replay) = &mut self.replay {
+                    replay.record(&self.game, swap);
+                }
+                if let Some(record) = self.game.history().last() {
+                    if self.game.mode().keeps_score() {
+                        log::debug!("{}", record.score);
+                    }
+                }
+                if shuffled {
+                    println!("No moves left! Shuffling the board.")
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // the results screen for the game just finished
+    fn game_over(&mut self, ctx: &mut Context) -> Transition {
+        print_game_over(&self.game);
+        if let Some(replay) = &mut self.replay {
+            replay.finish(&self.game);
+            match storage::save(storage::LAST_REPLAY, &replay.to_toml()) {
+                Ok(()) => println!(
+                    "Replay saved to {}",
+                    storage::location(storage::LAST_REPLAY)
+                ),
+                Err(error) => println!("Couldn't save the replay: {}", error),
+            }
+        }
+        let level = self.game.level();
+        let rank = if self.game.mode().keeps_score() {
+            let entry = HighScore {
+                score: self.game.score(),
+                player: ctx.settings.player.clone(),
+                date: date_string(storage::since_epoch().as_secs() / SECONDS_PER_DAY),
+                seed: self.game.seed(),
+            };
+            let rank = ctx.high_scores.record(level, entry);
+            if let Err(error) = storage::save(storage::HIGH_SCORES, &ctx.high_scores.to_toml()) {
+                println!("Couldn't save the high scores: {}", error);
+            }
+            rank
+        } else {
+            None
+        };
+        let table = ctx.high_scores.top(level).to_vec();
+        print_high_scores(&table, rank);
+
+        let stars = match self.game.outcome() {
+            Some(Outcome::Won { stars }) => stars,
+            _ => 0,
+        };
+        let results = Results {
+            stars,
+            score: self.game.score(),
+            best_combo: self.game.best_combo(),
+            table,
+            rank,
+        };
+        Transition::Push(Box::new(ResultsScreen::new(results, level.clone())))
+    }
+
+    // how far a space's candy has slid towards the one it was swapped with, if it is bouncing
+    fn bounce_offset(&self, space: (usize, usize)) -> [f32; 2] {
+        let Some(Bounce {
+            start,
+            first,
+            second,
+        }) = self.bounce
+        else {
+            return [0.0, 0.0];
+        };
+        let (from, to) = if space == first {
+            (first, second)
+        } else if space == second {
+            (second, first)
+        } else {
+            return [0.0, 0.0];
+        };
+        let progress = (start.elapsed().as_secs_f32() / BOUNCE_DURATION.as_secs_f32()).min(1.0);
+        let offset = (progress * std::f32::consts::PI).sin();
+        let from = self.game.grid().grid_to_screen(from.0, from.1);
+        let to = self.game.grid().grid_to_screen(to.0, to.1);
+        [(to[0] - from[0]) * offset, (to[1] - from[1]) * offset]
+    }
+}
+
+impl Screen for Playing {
+    fn enter(&mut self, ctx: &mut Context) {
+        // the board and every refill come from the seed, so a game can be played again from it
+        println!("Seed: {}", self.game.seed());
+        println!("Mode: {}", self.game.mode());
+        for objective in &self.game.level().objectives {
+            println!("Goal: {}", objective);
+        }
+        // the camera shows exactly the board and the HUD above it
+        let [width, height] = self.game.grid().screen_size();
+        ctx.view = [width, height + HUD_HEIGHT];
+        ctx.title = format!("candycrunch - seed {}", self.game.seed());
+    }
+
+    // a game left before it is over is saved, to be picked back up from the title, even in a
+    // later run
+    fn exit(&mut self, ctx: &mut Context) {
+        if self.game.is_over() {
+            return;
+        }
+        match storage::save(storage::SAVED_GAME, &self.game.to_save()) {
+            Ok(()) => println!("Game saved. Pick CONTINUE on the title screen to go on."),
+            Err(error) => println!("Couldn't save the game: {}", error),
+        }
+        ctx.saved_game = Some(self.game.clone());
+    }
+
+    // the clock stops while anything is shown over the game
+    fn cover(&mut self, _ctx: &mut Context) {
+        self.game.step(Action::Pause);
+    }
+
+    fn uncover(&mut self, _ctx: &mut Context) {
+        self.game.step(Action::Resume);
+        // the hint waits for the player to be back for a while
+        self.last_action = Instant::now();
+    }
+
+    // the game pauses itself when the player switches to another window
+    fn focus_lost(&mut self, _ctx: &mut Context) -> Transition {
+        if self.game.is_over() {
+            Transition::Stay
+        } else {
+            self.pause()
+        }
+    }
+
+    fn update(&mut self, ctx: &mut Context, frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) || ctx.key_pressed(Key::P) {
+            return self.pause();
+        }
+
+        self.game.step(Action::Tick(frame_time));
+        if self
+            .bounce
+            .is_some_and(|bounce| bounce.start.elapsed() >= BOUNCE_DURATION)
+        {
+            self.bounce = None;
+        }
+
+        // Ctrl+Z takes back the last move and Ctrl+Y makes it again. Not while a rejected swap is
+        // bouncing, since the bounce is drawn over the current board
+        let history_action = if self.bounce.is_some() || !ctx.input.is_ctrl_down() {
+            None
+        } else if ctx.key_pressed(Key::Z) {
+            Some(Action::Undo)
+        } else if ctx.key_pressed(Key::Y) {
+            Some(Action::Redo)
+        } else {
+            None
+        };
+        if let Some(action) = history_action {
+            if self.game.step(action) != StepResult::Ignored {
+                if let Some(replay) = &mut self.replay {
+                    replay.record(&self.game, action);
+                }
+                // the board changed under the player, so drop any half-made swap and the hint,
+                // which may no longer be on the board
+                self.act();
+                self.selected = None;
+            }
+        }
+
+        // clicks are ignored while a rejected swap is bouncing back
+        if self.bounce.is_none() && ctx.clicked() {
+            self.act();
+            self.click(ctx);
+        }
+
+        // the clock or the last move may have ended the game
+        if self.game.is_over() {
+            return self.game_over(ctx);
+        }
+
+        // after a while without a click, point out the best move left on the board
+        if self.hint.is_none()
+            && ctx
+                .settings
+                .hint_delay
+                .is_some_and(|delay| self.last_action.elapsed() >= delay)
+        {
+            self.hint = self.game.grid().best_move();
+        }
+        Transition::Stay
+    }
+
+    fn render(&self, _ctx: &Context, frame: &mut Frame) {
+        let grid = self.game.grid();
+        render_board(grid, frame, |space| self.bounce_offset(space));
+
+        if let Some((col, row)) = self.selected {
+            let [x, y] = grid.grid_to_screen(col, row);
+            frame.sprite([x, y, CELL_SIZE, CELL_SIZE], SELECTOR);
+        }
+
+        if let Some(Move { first, second }) = self.hint {
+            // the markers grow and shrink around the middle of their space
+            let pulse = (self.start_time.elapsed().as_secs_f32() * HINT_PULSE_SPEED).sin();
+            let size = CELL_SIZE * (1.0 + 0.15 * pulse);
+            let inset = (CELL_SIZE - size) / 2.0;
+            for (col, row) in [first, second] {
+                let [x, y] = grid.grid_to_screen(col, row);
+                frame.sprite([x + inset, y + inset, size, size], HINT_MARKER);
+            }
+        }
+
+        hud::render(&self.game, frame);
+    }
+}
+
+// draw every layer of the board, with each candy moved by `offset` from its space. Spaces that
+// would draw the transparent region, like those without jelly or a blocker, are skipped
+pub fn render_board(
+    grid: &GameGrid,
+    frame: &mut Frame,
+    offset: impl Fn((usize, usize)) -> [f32; 2],
+) {
+    let nothing = CandyColor::Empty.sheet_region();
+    for (layer, coords) in BOARD_LAYERS.iter().enumerate() {
+        for col in 0..grid.width() {
+            for row in 0..grid.height() {
+                let region = coords(grid, col, row);
+                if region == nothing {
+                    continue;
+                }
+                let [mut x, mut y] = grid.grid_to_screen(col, row);
+                // only the candies move, the jelly and blockers stay put
+                if layer == 0 {
+                    let [dx, dy] = offset((col, row));
+                    x += dx;
+                    y += dy;
+                }
+                frame.sprite([x, y, CELL_SIZE, CELL_SIZE], region);
+            }
+        }
+    }
+}
+
+fn print_high_scores(table: &[HighScore], rank: Option<usize>) {
+    if let Some(rank) = rank {
+        println!("New high score! #{}", rank + 1);
+    }
+    println!("High scores:");
+    for (index, high_score) in table.iter().enumerate() {
+        println!(
+            "{:>3}. {:>7}  {}  {}  seed {}",
+            index + 1,
+            high_score.score,
+            high_score.player,
+            high_score.date,
+            high_score.seed
+        );
+    }
+}
+
+fn print_game_over(game: &GameState) {
+    let bold_start = "\x1B[1m";
+    let red_start = "\x1B[31m";
+    let text_reset = "\x1B[0m"; // Reset text formatting
+
+    if game.mode() == GameMode::Endless {
+        println!("No moves left!");
+    }
+    match game.outcome() {
+        Some(Outcome::Won { stars }) if !game.level().objectives.is_empty() => {
+            println!(
+                "{}Level complete! {}/3 stars{}",
+                bold_start, stars, text_reset
+            )
+        }
+        Some(Outcome::Lost) => println!("{}Level failed!{}", bold_start, text_reset),
+        _ => {}
+    }
+    if game.bonus() > 0 {
+        match game.mode() {
+            GameMode::Moves(_) => println!("Moves bonus: {}", game.bonus()),
+            _ => println!("Time bonus: {}", game.bonus()),
+        }
+    }
+
+    // Print the entire line in bold
+    print!("{}Game Over! Final Score: ", bold_start);
+
+    // Print the score in red
+    print!("{}{}{}", red_start, game.score(), text_reset);
+
+    // Print a newline to end the line
+    println!();
+}
@@ -0,0 +1,94 @@
+use super::playing::render_board;
+use super::title::Title;
+use super::{Context, Frame, Screen, Transition, SHADE};
+use crate::font::Align;
+use crate::hud::{self, HUD_HEIGHT};
+use scene2d::replay::{Playback, ReplayError};
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+// how much faster than the game clock a replay goes while Space is held
+const FAST_FORWARD: u32 = 4;
+
+// a recorded game played back in real time, then checked against the score it recorded
+pub struct Replaying {
+    playback: Playback,
+    // how the replay came out, once it has played to the end
+    verdict: Option<Result<usize, ReplayError>>,
+}
+
+impl Replaying {
+    pub fn new(playback: Playback) -> Self {
+        Replaying {
+            playback,
+            verdict: None,
+        }
+    }
+}
+
+impl Screen for Replaying {
+    fn enter(&mut self, ctx: &mut Context) {
+        let replay = self.playback.replay();
+        println!(
+            "Replaying {} ({}) from seed {}: {} moves",
+            replay.level,
+            replay.mode,
+            replay.seed,
+            replay.actions.len()
+        );
+        let [width, height] = self.playback.game().grid().screen_size();
+        ctx.view = [width, height + HUD_HEIGHT];
+        ctx.title = format!("candycrunch - replay of seed {}", replay.seed);
+    }
+
+    // Escape goes back to the title, and so does Enter once the replay is over
+    fn update(&mut self, ctx: &mut Context, frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) || (self.verdict.is_some() && ctx.key_pressed(Key::Return))
+        {
+            return Transition::Reset(vec![Box::new(Title::new())]);
+        }
+        if self.verdict.is_some() {
+            return Transition::Stay;
+        }
+
+        let speed = if ctx.input.is_key_down(Key::Space) {
+            FAST_FORWARD
+        } else {
+            1
+        };
+        self.playback.advance(frame_time * speed);
+        if self.playback.is_finished() {
+            let verdict = self.playback.verify();
+            match &verdict {
+                Ok(score) => println!("Replay verified: final score {}", score),
+                Err(error) => println!("Replay failed to verify: {}", error),
+            }
+            self.verdict = Some(verdict);
+        }
+        Transition::Stay
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        let game = self.playback.game();
+        render_board(game.grid(), frame, |_| [0.0, 0.0]);
+        hud::render(game, frame);
+
+        // once it is over, the board is shaded and the verdict written across it
+        if let Some(verdict) = &self.verdict {
+            let board = ctx.board_rect();
+            let [x, y, width, height] = board;
+            frame.sprite(board, SHADE);
+            let text = match verdict {
+                Ok(_) => "VERIFIED",
+                Err(_) => "MISMATCH",
+            };
+            let size = (width / (text.len() + 2) as f32).min(height / 4.0);
+            frame.text(
+                text,
+                [x + width / 2.0, y + (height - size) / 2.0],
+                size,
+                Align::Center,
+            );
+        }
+    }
+}
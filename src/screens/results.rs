@@ -0,0 +1,276 @@
+use super::playing::Playing;
+use super::title::Title;
+use super::{Context, Frame, Screen, Transition, SHADE};
+use crate::font::Align;
+use scene2d::high_scores::HighScore;
+use scene2d::Level;
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+// the most digits a number on the results screen is drawn with. Anything bigger shows as all 9s
+const MAX_DIGITS: usize = 7;
+
+// how many high scores are listed, and how much of each player's name is shown
+const TABLE_LINES: usize = 5;
+const NAME_LENGTH: usize = 8;
+// a line of the table: the place, a marker for the score just played, the name and the score
+const TABLE_CHARS: usize = 2 + 1 + NAME_LENGTH + 1 + MAX_DIGITS;
+
+const STAR: [f32; 4] = [8.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const NO_STAR: [f32; 4] = [16.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const SCORE_ICON: [f32; 4] = [24.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const COMBO_ICON: [f32; 4] = [32.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const HIGH_SCORE_ICON: [f32; 4] = [40.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const PLAY_AGAIN_BUTTON: [f32; 4] = [48.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+const QUIT_BUTTON: [f32; 4] = [56.0 / 80.0, 144.0 / 160.0, 8.0 / 80.0, 8.0 / 160.0];
+
+// what a finished game is summed up with
+pub struct Results {
+    // stars won, or 0 if the game was lost
+    pub stars: usize,
+    pub score: usize,
+    // the most waves of clears a single move set off
+    pub best_combo: usize,
+    // the high scores for the level and mode, best first
+    pub table: Vec<HighScore>,
+    // where the game just played landed in the table, if it made it
+    pub rank: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ResultsChoice {
+    PlayAgain,
+    Quit,
+}
+
+// where everything on the screen goes, worked out from the size of the board: the stars, three
+// rows of numbers, the high score table, then the buttons
+struct Layout {
+    // the size of an icon or a digit
+    unit: f32,
+    row_height: f32,
+    // the size of the table's text and how far apart its lines are
+    table_size: f32,
+    table_line: f32,
+    // the top of the first row
+    top: f32,
+    left: f32,
+    width: f32,
+}
+
+impl Layout {
+    fn new(ctx: &Context) -> Self {
+        let [left, bottom, width, height] = ctx.board_rect();
+        // everything is sized in units that fit the widest row across any board
+        let unit = (width / (MAX_DIGITS as f32 + 2.0)).min(height / 13.0);
+        let row_height = 1.5 * unit;
+        let table_size = (0.75 * unit).min(width / (TABLE_CHARS + 2) as f32);
+        let mut layout = Layout {
+            unit,
+            row_height,
+            table_size,
+            table_line: 1.25 * table_size,
+            top: 0.0,
+            left,
+            width,
+        };
+        layout.top = bottom + (height + layout.height()) / 2.0;
+        layout
+    }
+
+    // the height of the table, with a gap under it
+    fn table_height(&self) -> f32 {
+        TABLE_LINES as f32 * self.table_line + self.unit / 2.0
+    }
+
+    fn height(&self) -> f32 {
+        5.0 * self.row_height + self.table_height()
+    }
+
+    // the bottom of a line of the table
+    fn table_line_y(&self, line: usize) -> f32 {
+        self.top - 4.0 * self.row_height - (line + 1) as f32 * self.table_line
+    }
+
+    // the buttons side by side along the bottom, as [x, y, width, height]
+    fn buttons(&self) -> [(ResultsChoice, [f32; 4]); 2] {
+        let size = self.row_height;
+        let y = self.top - self.height();
+        let middle = self.left + self.width / 2.0;
+        [
+            (
+                ResultsChoice::PlayAgain,
+                [middle - 1.5 * size, y, size, size],
+            ),
+            (ResultsChoice::Quit, [middle + 0.5 * size, y, size, size]),
+        ]
+    }
+}
+
+// shown over the board once a game is over, with the stars won, the final score, the best combo,
+// the high score and the top of the high score table, and buttons to play the level again or go
+// back to the title
+pub struct ResultsScreen {
+    results: Results,
+    level: Level,
+}
+
+impl ResultsScreen {
+    // `level` is the level as it was played, to play again
+    pub fn new(results: Results, level: Level) -> Self {
+        ResultsScreen { results, level }
+    }
+
+    // the high scores to list with their places. A score just played that made the table but
+    // not the top of it is listed last, in place of the one above it
+    fn table_lines(&self) -> Vec<(usize, &HighScore)> {
+        let table = &self.results.table;
+        let mut lines: Vec<_> = table.iter().enumerate().take(TABLE_LINES).collect();
+        if let Some(rank) = self.results.rank.filter(|&rank| rank >= TABLE_LINES) {
+            lines[TABLE_LINES - 1] = (rank, &table[rank]);
+        }
+        lines
+    }
+
+    // the button under the mouse
+    fn button_at(ctx: &Context) -> Option<ResultsChoice> {
+        let [x, y] = ctx.mouse;
+        Layout::new(ctx)
+            .buttons()
+            .into_iter()
+            .find(|(_, [left, bottom, width, height])| {
+                x >= *left && x < left + width && y >= *bottom && y < bottom + height
+            })
+            .map(|(choice, _)| choice)
+    }
+}
+
+impl Screen for ResultsScreen {
+    // Enter plays again and Escape goes back to the title, like the buttons
+    fn update(&mut self, ctx: &mut Context, _frame_time: Duration) -> Transition {
+        let choice = if ctx.clicked() {
+            ResultsScreen::button_at(ctx)
+        } else if ctx.key_pressed(Key::Return) {
+            Some(ResultsChoice::PlayAgain)
+        } else if ctx.key_pressed(Key::Escape) {
+            Some(ResultsChoice::Quit)
+        } else {
+            None
+        };
+        match choice {
+            // a new board from a new seed, on the same level and mode
+            Some(ResultsChoice::PlayAgain) => {
+                let seed = ctx.settings.take_seed();
+                Transition::Reset(vec![
+                    Box::new(Title::new()),
+                    Box::new(Playing::new(self.level.clone(), seed)),
+                ])
+            }
+            Some(ResultsChoice::Quit) => Transition::Reset(vec![Box::new(Title::new())]),
+            None => Transition::Stay,
+        }
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        let layout = Layout::new(ctx);
+        let Layout {
+            unit,
+            row_height,
+            table_size,
+            top,
+            left,
+            width,
+            ..
+        } = layout;
+        frame.sprite(ctx.board_rect(), SHADE);
+
+        // the stars along the top, centered
+        let stars_left = left + (width - 2.0 * row_height - unit) / 2.0;
+        for star in 0..3 {
+            frame.sprite(
+                [
+                    stars_left + star as f32 * row_height,
+                    top - row_height,
+                    unit,
+                    unit,
+                ],
+                if star < self.results.stars {
+                    STAR
+                } else {
+                    NO_STAR
+                },
+            );
+        }
+
+        // the best score there is, which may be the one just played
+        let high_score = self
+            .results
+            .table
+            .first()
+            .map_or(self.results.score, |best| {
+                best.score.max(self.results.score)
+            });
+
+        // an icon and a number on each row, lined up on the left so the numbers start together
+        let rows = [
+            (SCORE_ICON, self.results.score),
+            (COMBO_ICON, self.results.best_combo),
+            (HIGH_SCORE_ICON, high_score),
+        ];
+        let row_left = left + (width - (MAX_DIGITS as f32 + 1.5) * unit) / 2.0;
+        for (row, (icon, number)) in rows.into_iter().enumerate() {
+            let y = top - (row as f32 + 2.0) * row_height;
+            frame.sprite([row_left, y, unit, unit], icon);
+            frame.text(
+                &capped(number).to_string(),
+                [row_left + 1.5 * unit, y],
+                unit,
+                Align::Left,
+            );
+        }
+
+        let table_left = left + (width - TABLE_CHARS as f32 * table_size) / 2.0;
+        for (line, (rank, high_score)) in self.table_lines().into_iter().enumerate() {
+            let marker = if Some(rank) == self.results.rank {
+                '>'
+            } else {
+                ' '
+            };
+            let name: String = high_score.player.chars().take(NAME_LENGTH).collect();
+            let text = format!(
+                "{:>2}{}{:<name_length$} {:>digits$}",
+                rank + 1,
+                marker,
+                name,
+                capped(high_score.score),
+                name_length = NAME_LENGTH,
+                digits = MAX_DIGITS
+            );
+            frame.text(
+                &text,
+                [table_left, layout.table_line_y(line)],
+                table_size,
+                Align::Left,
+            );
+        }
+
+        for (choice, region) in layout.buttons() {
+            frame.sprite(
+                region,
+                match choice {
+                    ResultsChoice::PlayAgain => PLAY_AGAIN_BUTTON,
+                    ResultsChoice::Quit => QUIT_BUTTON,
+                },
+            );
+        }
+    }
+
+    fn is_opaque(&self) -> bool {
+        false
+    }
+}
+
+// a number capped to what fits on the screen
+fn capped(number: usize) -> usize {
+    number.min(10usize.pow(MAX_DIGITS as u32) - 1)
+}
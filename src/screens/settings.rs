@@ -0,0 +1,84 @@
+use super::menu::Menu;
+use super::{Context, Frame, Screen, Transition};
+use scene2d::level::{GAME_DURATION, MOVE_LIMIT};
+use scene2d::GameMode;
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+const MODE: usize = 0;
+const HINTS: usize = 1;
+
+// the modes the settings go through, None being each level's own
+const MODES: [Option<GameMode>; 5] = [
+    None,
+    Some(GameMode::Timed(GAME_DURATION)),
+    Some(GameMode::Moves(MOVE_LIMIT)),
+    Some(GameMode::Endless),
+    Some(GameMode::Zen),
+];
+
+// the hint delays the settings go through, None being no hints
+const HINT_DELAYS: [Option<Duration>; 4] = [
+    Some(Duration::from_secs(2)),
+    Some(Duration::from_secs(5)),
+    Some(Duration::from_secs(10)),
+    None,
+];
+
+// choosing a setting moves it on to the next choice, going back around after the last
+pub struct SettingsScreen {
+    menu: Menu,
+}
+
+impl SettingsScreen {
+    pub fn new(ctx: &Context) -> Self {
+        let mut screen = SettingsScreen {
+            menu: Menu::new(vec![String::new(), String::new(), "BACK".to_string()]),
+        };
+        screen.describe(ctx);
+        screen
+    }
+
+    // write the current settings into the menu
+    fn describe(&mut self, ctx: &Context) {
+        let mode = match ctx.settings.mode {
+            None => "LEVEL".to_string(),
+            Some(GameMode::Timed(limit)) => format!("{}S", limit.as_secs()),
+            Some(GameMode::Moves(limit)) => format!("{} MOVES", limit),
+            Some(GameMode::Endless) => "ENDLESS".to_string(),
+            Some(GameMode::Zen) => "ZEN".to_string(),
+        };
+        self.menu.set_item(MODE, format!("MODE: {}", mode));
+        let hints = match ctx.settings.hint_delay {
+            Some(delay) => format!("{}S", delay.as_secs_f32()),
+            None => "OFF".to_string(),
+        };
+        self.menu.set_item(HINTS, format!("HINTS: {}", hints));
+    }
+}
+
+// the choice after `current`, or the first if `current` isn't one of them
+fn next<T: Copy + PartialEq>(choices: &[T], current: T) -> T {
+    let index = choices.iter().position(|&choice| choice == current);
+    choices[index.map_or(0, |index| (index + 1) % choices.len())]
+}
+
+impl Screen for SettingsScreen {
+    fn update(&mut self, ctx: &mut Context, _frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) {
+            return Transition::Pop;
+        }
+        match self.menu.update(ctx, ctx.view_rect()) {
+            Some(MODE) => ctx.settings.mode = next(&MODES, ctx.settings.mode),
+            Some(HINTS) => ctx.settings.hint_delay = next(&HINT_DELAYS, ctx.settings.hint_delay),
+            Some(_) => return Transition::Pop,
+            None => return Transition::Stay,
+        }
+        self.describe(ctx);
+        Transition::Stay
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        self.menu.render(frame, ctx.view_rect());
+    }
+}
@@ -0,0 +1,112 @@
+use super::level_select::LevelSelect;
+use super::menu::Menu;
+use super::paused::Paused;
+use super::playing::Playing;
+use super::settings::SettingsScreen;
+use super::{Context, Frame, Screen, Transition};
+use crate::font::Align;
+use crate::storage;
+use scene2d::grid::CELL_SIZE;
+use std::time::Duration;
+use winit::event::VirtualKeyCode as Key;
+
+#[derive(Copy, Clone)]
+enum Item {
+    Continue,
+    Play,
+    Settings,
+    Quit,
+}
+
+// the first screen, with the name of the game over a menu
+pub struct Title {
+    menu: Menu,
+    // what each line of the menu does. CONTINUE is only there while there is a game to go on with
+    items: Vec<Item>,
+}
+
+impl Title {
+    pub fn new() -> Self {
+        Title {
+            menu: Menu::new(Vec::new()),
+            items: Vec::new(),
+        }
+    }
+
+    // build the menu for whether there is a saved game, with the first item picked out
+    fn refresh(&mut self, ctx: &mut Context) {
+        ctx.title = "candycrunch".to_string();
+        self.items = ctx
+            .saved_game
+            .is_some()
+            .then_some(Item::Continue)
+            .into_iter()
+            .chain([Item::Play, Item::Settings, Item::Quit])
+            .collect();
+        let names = self.items.iter().map(|item| {
+            match item {
+                Item::Continue => "CONTINUE",
+                Item::Play => "PLAY",
+                Item::Settings => "SETTINGS",
+                Item::Quit => "QUIT",
+            }
+            .to_string()
+        });
+        self.menu = Menu::new(names.collect());
+    }
+
+    // the menu takes the lower two thirds of the view, under the name
+    fn menu_area(ctx: &Context) -> [f32; 4] {
+        let [x, y, width, height] = ctx.view_rect();
+        [x, y, width, height * 2.0 / 3.0]
+    }
+}
+
+impl Screen for Title {
+    fn enter(&mut self, ctx: &mut Context) {
+        self.refresh(ctx);
+    }
+
+    fn uncover(&mut self, ctx: &mut Context) {
+        self.refresh(ctx);
+    }
+
+    fn update(&mut self, ctx: &mut Context, _frame_time: Duration) -> Transition {
+        if ctx.key_pressed(Key::Escape) {
+            return Transition::Quit;
+        }
+        let chosen = self.menu.update(ctx, Title::menu_area(ctx));
+        match chosen.map(|index| self.items[index]) {
+            // the game comes back paused, so the player can find their place before the clock
+            // starts again. Once taken up it is no longer saved, until it is left again
+            Some(Item::Continue) => match ctx.saved_game.take() {
+                Some(game) => {
+                    storage::remove(storage::SAVED_GAME);
+                    Transition::Reset(vec![
+                        Box::new(Title::new()),
+                        Box::new(Playing::resumed(game)),
+                        Box::new(Paused::new()),
+                    ])
+                }
+                None => Transition::Stay,
+            },
+            Some(Item::Play) => Transition::Push(Box::new(LevelSelect::new(ctx))),
+            Some(Item::Settings) => Transition::Push(Box::new(SettingsScreen::new(ctx))),
+            Some(Item::Quit) => Transition::Quit,
+            None => Transition::Stay,
+        }
+    }
+
+    fn render(&self, ctx: &Context, frame: &mut Frame) {
+        let [x, y, width, height] = ctx.view_rect();
+        let name = "CANDYCRUNCH";
+        let size = CELL_SIZE.min(width / (name.len() + 1) as f32);
+        frame.text(
+            name,
+            [x + width / 2.0, y + height * 5.0 / 6.0 - size / 2.0],
+            size,
+            Align::Center,
+        );
+        self.menu.render(frame, Title::menu_area(ctx));
+    }
+}
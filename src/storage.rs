@@ -0,0 +1,107 @@
+// Small text files kept between runs: in the platform's data directory on native, and in
+// localStorage in the browser, under the same names
+use std::time::Duration;
+
+// the names things are saved under
+pub const HIGH_SCORES: &str = "high_scores.toml";
+pub const LAST_REPLAY: &str = "last_replay.toml";
+pub const SAVED_GAME: &str = "saved_game.toml";
+
+// the folder the game keeps its files in, inside the platform's data directory
+#[cfg(not(target_arch = "wasm32"))]
+const APP_DIR: &str = "candycrunch";
+
+// the platform's data directory: %APPDATA% on Windows, ~/Library/Application Support on macOS
+// and $XDG_DATA_HOME or ~/.local/share everywhere else
+#[cfg(not(target_arch = "wasm32"))]
+fn data_dir() -> Option<std::path::PathBuf> {
+    use std::env::var_os;
+    use std::path::PathBuf;
+    let home = || var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".local/share")))
+    };
+    Some(base?.join(APP_DIR))
+}
+
+// the text saved under `name`, or None if nothing has been
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(name: &str) -> Option<String> {
+    std::fs::read_to_string(data_dir()?.join(name)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(name: &str, text: &str) -> Result<(), String> {
+    let dir = data_dir().ok_or("there is no data directory to save to")?;
+    std::fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+    std::fs::write(dir.join(name), text).map_err(|error| error.to_string())
+}
+
+// forget what was saved under `name`, if anything was
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(name: &str) {
+    if let Some(dir) = data_dir() {
+        let _ = std::fs::remove_file(dir.join(name));
+    }
+}
+
+// where something saved under `name` can be found, to tell the player
+#[cfg(not(target_arch = "wasm32"))]
+pub fn location(name: &str) -> String {
+    match data_dir() {
+        Some(dir) => dir.join(name).display().to_string(),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(name: &str) -> Option<String> {
+    local_storage()?.get_item(name).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn location(name: &str) -> String {
+    format!("localStorage[\"{}\"]", name)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(name: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(name);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(name: &str, text: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or("localStorage isn't available")?
+        .set_item(name, text)
+        .map_err(|_| "localStorage is full or turned off".to_string())
+}
+
+// how long it has been since 1970-01-01 UTC
+pub fn since_epoch() -> Duration {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+    // the browser has no system clock for std to read
+    #[cfg(target_arch = "wasm32")]
+    {
+        Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+}